@@ -1,3 +1,6 @@
 fn main() {
-    tauri_build::build()
+    // 仅在启用桌面界面（gui feature）时生成 Tauri 上下文，CLI 单独构建时跳过
+    if std::env::var_os("CARGO_FEATURE_GUI").is_some() {
+        tauri_build::build()
+    }
 }
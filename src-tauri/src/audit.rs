@@ -0,0 +1,212 @@
+use crate::config::get_app_config_dir;
+use crate::error::AppError;
+use crate::models::{
+    AuditAction, AuditFilter, AuditOutcome, AuditRecord, AuditSource, BalanceInfo, ImportReport,
+    ImportStatus,
+};
+use crate::provider_manager::load_config;
+use chrono::{DateTime, FixedOffset, Utc};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// 获取审计日志路径 (~/.factory-ai-droid-switch/audit.jsonl)
+pub fn get_audit_log_path() -> PathBuf {
+    get_app_config_dir().join("audit.jsonl")
+}
+
+/// 审计记录涉及的密钥或模型
+#[derive(Debug, Clone, Default)]
+pub struct AuditTarget {
+    pub provider_id: Option<String>,
+    pub provider_name: Option<String>,
+    pub model_id: Option<String>,
+}
+
+impl AuditTarget {
+    /// 按 ID 查找密钥名称（删除等操作需在执行前调用）
+    pub fn provider(id: &str) -> Self {
+        let provider_name = load_config().ok().and_then(|config| {
+            config
+                .providers
+                .into_iter()
+                .find(|p| p.id == id)
+                .map(|p| p.name)
+        });
+        Self {
+            provider_id: Some(id.to_string()),
+            provider_name,
+            model_id: None,
+        }
+    }
+
+    /// 当前激活的密钥
+    pub fn active_provider() -> Self {
+        load_config()
+            .ok()
+            .and_then(|config| config.active_provider_id)
+            .map(|id| Self::provider(&id))
+            .unwrap_or_default()
+    }
+
+    /// 已知 ID 和名称的密钥
+    pub fn named(id: Option<String>, name: String) -> Self {
+        Self {
+            provider_id: id,
+            provider_name: Some(name),
+            model_id: None,
+        }
+    }
+
+    pub fn model(id: &str) -> Self {
+        Self {
+            model_id: Some(id.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+/// 追加一条审计记录；写入失败只记录日志，不影响原操作
+pub fn append(record: &AuditRecord) {
+    if let Err(e) = try_append(record) {
+        log::warn!("写入审计日志失败: {}", e);
+    }
+}
+
+fn try_append(record: &AuditRecord) -> Result<(), AppError> {
+    let path = get_audit_log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
+    }
+
+    let mut line =
+        serde_json::to_string(record).map_err(|e| AppError::parse("序列化审计记录失败", e))?;
+    line.push('\n');
+
+    // 整行一次写入，多个进程同时追加时不会交错
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| AppError::io("打开审计日志失败", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| AppError::io("写入审计日志失败", e))
+}
+
+/// 根据操作结果生成并追加审计记录
+pub fn record<T>(
+    action: AuditAction,
+    source: AuditSource,
+    target: AuditTarget,
+    detail: Option<String>,
+    result: &Result<T, AppError>,
+) {
+    let (outcome, error) = match result {
+        Ok(_) => (AuditOutcome::Success, None),
+        Err(e) => (AuditOutcome::Failure, Some(e.to_string())),
+    };
+    append(&AuditRecord {
+        timestamp: Utc::now().to_rfc3339(),
+        action,
+        source,
+        provider_id: target.provider_id,
+        provider_name: target.provider_name,
+        model_id: target.model_id,
+        outcome,
+        detail,
+        error,
+    });
+}
+
+/// 记录余额刷新结果
+pub fn record_balance(
+    source: AuditSource,
+    target: AuditTarget,
+    result: &Result<BalanceInfo, AppError>,
+) {
+    let detail = result.as_ref().ok().map(|b| {
+        format!(
+            "剩余 {} / {}（已用 {:.1}%）",
+            b.remaining, b.allowance, b.percent_used
+        )
+    });
+    record(AuditAction::RefreshBalance, source, target, detail, result);
+}
+
+/// 记录导入结果：每一行对应一条添加密钥记录，跳过和无效的行记为失败
+pub fn record_import(source: AuditSource, report: &ImportReport) {
+    let timestamp = Utc::now().to_rfc3339();
+    for row in &report.rows {
+        let (outcome, error) = match row.status {
+            ImportStatus::Added => (AuditOutcome::Success, None),
+            ImportStatus::SkippedDuplicate | ImportStatus::Invalid => {
+                (AuditOutcome::Failure, row.message.clone())
+            }
+        };
+        append(&AuditRecord {
+            timestamp: timestamp.clone(),
+            action: AuditAction::AddProvider,
+            source,
+            provider_id: row.provider_id.clone(),
+            provider_name: Some(row.name.clone()).filter(|n| !n.is_empty()),
+            model_id: None,
+            outcome,
+            detail: Some(format!("导入第 {} 行", row.line)),
+            error,
+        });
+    }
+}
+
+/// 读取审计日志，按时间倒序返回符合条件的记录
+pub fn get_audit_log(filter: &AuditFilter) -> Result<Vec<AuditRecord>, AppError> {
+    let path = get_audit_log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let parse_time = |value: &Option<String>| -> Result<Option<DateTime<FixedOffset>>, AppError> {
+        value
+            .as_deref()
+            .map(|v| {
+                DateTime::parse_from_rfc3339(v)
+                    .map_err(|e| AppError::invalid_input(format!("无效的时间 {}: {}", v, e)))
+            })
+            .transpose()
+    };
+    let since = parse_time(&filter.since)?;
+    let until = parse_time(&filter.until)?;
+
+    let file = fs::File::open(&path).map_err(|e| AppError::io("打开审计日志失败", e))?;
+    let mut records: Vec<AuditRecord> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<AuditRecord>(&line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::warn!("跳过无法解析的审计记录: {}", e);
+                None
+            }
+        })
+        .filter(|r| filter.action.is_none_or(|a| r.action == a))
+        .filter(|r| filter.source.is_none_or(|s| r.source == s))
+        .filter(|r| filter.outcome.is_none_or(|o| r.outcome == o))
+        .filter(|r| {
+            filter.provider.as_ref().is_none_or(|p| {
+                r.provider_id.as_ref() == Some(p) || r.provider_name.as_ref() == Some(p)
+            })
+        })
+        .filter(|r| {
+            let Ok(time) = DateTime::parse_from_rfc3339(&r.timestamp) else {
+                return since.is_none() && until.is_none();
+            };
+            since.is_none_or(|s| time >= s) && until.is_none_or(|u| time <= u)
+        })
+        .collect();
+
+    records.reverse();
+    if let Some(limit) = filter.limit {
+        records.truncate(limit);
+    }
+    Ok(records)
+}
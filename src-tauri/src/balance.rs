@@ -1,138 +1,303 @@
-use crate::models::BalanceInfo;
-use serde::Deserialize;
+use crate::config::run_blocking;
+use crate::error::AppError;
+use crate::factory_client::{FactoryApiError, FactoryClient, HttpFactoryClient};
+use crate::models::{
+    AppConfig, BalanceCheckResult, BalanceCheckSettings, BalanceInfo, BalanceRefreshSettings,
+    Provider,
+};
+use crate::provider_manager::{load_config, record_balance_results, update_config};
+use crate::secret_store::resolve_api_key;
+use crate::vault;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use std::collections::HashMap;
+use std::time::Duration;
 
-/// Factory.ai API 响应结构
-#[derive(Debug, Deserialize)]
-struct FactoryApiResponse {
-    usage: Usage,
+/// 最多同时查询的密钥数量
+const MAX_CONCURRENCY: usize = 32;
+
+/// Retry-After 最长等待时间
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 按应用配置创建 Factory.ai 客户端
+pub fn default_client() -> Result<HttpFactoryClient, AppError> {
+    HttpFactoryClient::from_config(&load_config()?)
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct Usage {
-    standard: StandardUsage,
-    #[serde(rename = "endDate")]
-    end_date: Option<i64>,
+/// 获取批量余额查询设置
+pub fn get_check_settings() -> Result<BalanceCheckSettings, AppError> {
+    Ok(load_config()?.balance_check)
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct StandardUsage {
-    user_tokens: u64,
-    total_allowance: u64,
-    org_overage_used: u64,
-    used_ratio: f64,
+/// 保存批量余额查询设置
+pub fn set_check_settings(settings: BalanceCheckSettings) -> Result<(), AppError> {
+    if !(1..=MAX_CONCURRENCY).contains(&settings.concurrency) {
+        return Err(AppError::invalid_input(format!(
+            "并发数必须在 1 到 {} 之间",
+            MAX_CONCURRENCY
+        )));
+    }
+
+    update_config(|config| {
+        config.balance_check = settings;
+        Ok(())
+    })?;
+    log::info!("已更新批量余额查询设置");
+    Ok(())
 }
 
-/// 查询单个 API Key 的余额
-pub async fn check_balance(api_key: &str) -> Result<BalanceInfo, String> {
-    log::info!(
-        "开始查询余额，API Key: {}...",
-        &api_key[..api_key.len().min(10)]
-    );
+/// 获取后台定时刷新设置
+pub fn get_refresh_settings() -> Result<BalanceRefreshSettings, AppError> {
+    Ok(load_config()?.balance_refresh)
+}
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(15))
-        .build()
-        .map_err(|e| {
-            log::error!("创建 HTTP 客户端失败: {}", e);
-            format!("创建 HTTP 客户端失败: {}", e)
-        })?;
-
-    log::info!("发送请求到 Factory.ai API...");
-    let response = client
-        .get("https://app.factory.ai/api/organization/members/chat-usage")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("x-factory-client", "web-browser")
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("请求失败: {}", e);
-            format!("请求失败: {}", e)
-        })?;
-
-    let status = response.status();
-    log::info!("收到响应，状态码: {}", status);
-
-    if status.as_u16() != 200 {
-        let text = response.text().await.unwrap_or_default();
-        log::error!("HTTP 错误 {}: {}", status, text);
-        return Err(format!("HTTP {}: {}", status, text));
+/// 保存后台定时刷新设置
+pub fn set_refresh_settings(settings: BalanceRefreshSettings) -> Result<(), AppError> {
+    if settings.interval_minutes == 0 {
+        return Err(AppError::invalid_input("刷新间隔必须大于 0 分钟"));
+    }
+    if settings.stale_after_minutes == 0 {
+        return Err(AppError::invalid_input("过时时间必须大于 0 分钟"));
     }
 
-    // 先获取响应文本用于调试
-    let response_text = response.text().await.map_err(|e| {
-        log::error!("读取响应文本失败: {}", e);
-        format!("读取响应失败: {}", e)
+    update_config(|config| {
+        config.balance_refresh = settings;
+        Ok(())
     })?;
+    log::info!("已更新后台定时刷新设置");
+    Ok(())
+}
+
+/// 格式化 token 数量（如 2.1M、350.0K）
+pub fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        t if t >= 1_000_000 => format!("{:.1}M", t as f64 / 1_000_000.0),
+        t if t >= 1_000 => format!("{:.1}K", t as f64 / 1_000.0),
+        t => t.to_string(),
+    }
+}
 
-    log::info!("响应内容: {}", response_text);
+/// 最近一次成功查询余额的时间（旧配置没有记录时使用更新时间）
+pub fn checked_at(provider: &Provider) -> Option<DateTime<Utc>> {
+    provider.balance.as_ref()?;
+    provider
+        .balance_checked_at
+        .as_deref()
+        .or(provider.updated_at.as_deref())
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
 
-    // 解析 JSON
-    let api_response: FactoryApiResponse = serde_json::from_str(&response_text).map_err(|e| {
-        log::error!("JSON 解析失败: {}，响应内容: {}", e, response_text);
-        format!("解析响应失败: {}，原始响应: {}", e, response_text)
-    })?;
+/// 密钥的余额是否在 ttl 内查询过
+pub fn is_fresh(provider: &Provider, ttl: chrono::Duration) -> bool {
+    checked_at(provider).is_some_and(|t| Utc::now() - t < ttl)
+}
 
-    let usage = api_response.usage.standard;
-    let remaining = usage.total_allowance.saturating_sub(usage.user_tokens);
-    let percent_used = usage.used_ratio * 100.0;
-    let exceeded = usage.used_ratio > 1.0;
+/// 标记超过设置时间未成功查询的余额为已过时
+pub fn mark_stale(config: &mut AppConfig, now: DateTime<Utc>) {
+    let stale_after = chrono::Duration::minutes(config.balance_refresh.stale_after_minutes as i64);
+    for provider in &mut config.providers {
+        provider.balance_stale = provider.balance.is_some()
+            && checked_at(provider).is_none_or(|t| now - t >= stale_after);
+    }
+}
 
-    // 提取到期时间：endDate字段（Unix时间戳，毫秒）
-    let expiry_date = api_response.usage.end_date.map(|timestamp| {
-        // 将毫秒时间戳转换为ISO格式字符串
-        use chrono::{TimeZone, Utc};
-        let datetime = Utc.timestamp_millis_opt(timestamp).unwrap();
-        datetime.to_rfc3339()
-    });
+/// 余额的查询时间距今多久（如“5 分钟前”），没有余额时为空
+pub fn format_age(provider: &Provider, now: DateTime<Utc>) -> Option<String> {
+    let minutes = (now - checked_at(provider)?).num_minutes().max(0);
+    Some(match minutes {
+        0 => "刚刚".to_string(),
+        m if m < 60 => format!("{} 分钟前", m),
+        m if m < 24 * 60 => format!("{} 小时前", m / 60),
+        m => format!("{} 天前", m / (24 * 60)),
+    })
+}
+
+/// 查询单个 API Key 的余额
+pub async fn check_balance(api_key: &str) -> Result<BalanceInfo, AppError> {
+    let client = run_blocking(default_client).await?;
+    check_balance_with(&client, api_key).await
+}
+
+/// 使用指定客户端查询单个 API Key 的余额
+pub async fn check_balance_with(
+    client: &dyn FactoryClient,
+    api_key: &str,
+) -> Result<BalanceInfo, AppError> {
+    log::info!(
+        "开始查询余额，API Key: {}...",
+        &api_key[..api_key.len().min(10)]
+    );
+
+    let balance = client.chat_usage(api_key).await?;
 
     log::info!(
         "余额查询成功: 已用 {}, 总配额 {}, 剩余 {}",
-        usage.user_tokens,
-        usage.total_allowance,
-        remaining
+        balance.used,
+        balance.allowance,
+        balance.remaining
     );
 
-    if let Some(ref expiry) = expiry_date {
+    if let Some(ref expiry) = balance.expiry_date {
         log::info!("到期时间: {}", expiry);
     }
 
-    Ok(BalanceInfo {
-        used: usage.user_tokens,
-        allowance: usage.total_allowance,
-        remaining,
-        overage: usage.org_overage_used,
-        used_ratio: usage.used_ratio,
-        percent_used,
-        exceeded,
-        expiry_date,
-    })
+    Ok(balance)
+}
+
+/// 第 attempt 次重试前的等待时间：429 优先使用 Retry-After，否则指数退避
+fn retry_delay(error: &FactoryApiError, attempt: u32, settings: &BalanceCheckSettings) -> Duration {
+    if let Some(retry_after) = error.retry_after() {
+        return retry_after.min(MAX_RETRY_AFTER);
+    }
+    Duration::from_millis(
+        settings
+            .retry_base_delay_ms
+            .saturating_mul(1 << attempt.min(16)),
+    )
+}
+
+/// 查询余额，网络错误、5xx 和 429 时按设置重试
+pub async fn check_balance_with_retry(
+    client: &dyn FactoryClient,
+    api_key: &str,
+    settings: &BalanceCheckSettings,
+) -> Result<BalanceInfo, AppError> {
+    let mut attempt = 0;
+    loop {
+        match client.chat_usage(api_key).await {
+            Ok(balance) => return Ok(balance),
+            Err(e) if e.is_retryable() && attempt < settings.max_retries => {
+                let delay = retry_delay(&e, attempt, settings);
+                attempt += 1;
+                log::warn!(
+                    "余额查询失败（{}），{} 毫秒后第 {} 次重试",
+                    e,
+                    delay.as_millis(),
+                    attempt
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// 并发查询多个密钥的余额（并发数由设置限制），返回顺序与输入无关
+///
+/// 每项为（调用方的标识，API Key）。
+pub async fn check_many<K>(
+    client: &dyn FactoryClient,
+    items: Vec<(K, String)>,
+    settings: &BalanceCheckSettings,
+) -> Vec<(K, Result<BalanceInfo, AppError>)> {
+    check_many_with_progress(client, items, settings, |_, _| {}).await
+}
+
+/// 同 [`check_many`]，每个密钥查询完成时调用 `on_result`
+pub async fn check_many_with_progress<K>(
+    client: &dyn FactoryClient,
+    items: Vec<(K, String)>,
+    settings: &BalanceCheckSettings,
+    mut on_result: impl FnMut(&K, &Result<BalanceInfo, AppError>),
+) -> Vec<(K, Result<BalanceInfo, AppError>)> {
+    let concurrency = settings.concurrency.clamp(1, MAX_CONCURRENCY);
+    stream::iter(items)
+        .map(|(id, api_key)| async move {
+            let result = check_balance_with_retry(client, &api_key, settings).await;
+            (id, result)
+        })
+        .buffer_unordered(concurrency)
+        .inspect(|(id, result)| on_result(id, result))
+        .collect()
+        .await
 }
 
 /// 批量查询 API Keys 的余额
 pub async fn batch_check_balances(
     api_keys: Vec<String>,
-) -> Result<HashMap<String, BalanceInfo>, String> {
-    let mut results = HashMap::new();
+) -> Result<HashMap<String, BalanceCheckResult>, AppError> {
+    let config = run_blocking(load_config).await?;
+    let client = HttpFactoryClient::from_config(&config)?;
+    Ok(batch_check_balances_with(&client, api_keys, &config.balance_check).await)
+}
 
-    for (i, key) in api_keys.iter().enumerate() {
-        match check_balance(key).await {
-            Ok(balance) => {
-                results.insert(key.clone(), balance);
+/// 使用指定客户端批量查询 API Keys 的余额，每个 Key 单独返回结果或错误
+pub async fn batch_check_balances_with(
+    client: &dyn FactoryClient,
+    api_keys: Vec<String>,
+    settings: &BalanceCheckSettings,
+) -> HashMap<String, BalanceCheckResult> {
+    let items = api_keys.into_iter().map(|k| (k.clone(), k)).collect();
+    check_many(client, items, settings)
+        .await
+        .into_iter()
+        .map(|(key, result)| {
+            if let Err(e) = &result {
+                log::warn!("查询密钥 {}... 余额失败: {}", &key[..key.len().min(10)], e);
             }
+            (key, result.into())
+        })
+        .collect()
+}
+
+/// 并发查询密钥余额并一次性写入配置
+///
+/// 只查询 `filter` 返回 true 的密钥，每个密钥查询完成时调用 `on_result`（参数为密钥、结果和本轮总数）。
+/// 返回每个密钥及其查询结果（无法解析 API Key 也算失败）。
+/// 读取配置、解析 API Key 和写入结果都在阻塞线程池中执行。
+pub async fn refresh_providers(
+    filter: impl Fn(&Provider) -> bool,
+    mut on_result: impl FnMut(&Provider, &Result<BalanceInfo, AppError>, usize),
+) -> Result<Vec<(Provider, Result<BalanceInfo, AppError>)>, AppError> {
+    let config = run_blocking(load_config).await?;
+    vault::ensure_unlocked(&config)?;
+    let client = HttpFactoryClient::from_config(&config)?;
+
+    let providers: Vec<Provider> = config.providers.into_iter().filter(|p| filter(p)).collect();
+    let total = providers.len();
+
+    let to_resolve = providers.clone();
+    let api_keys: Vec<Result<String, AppError>> =
+        run_blocking(move || Ok(to_resolve.iter().map(resolve_api_key).collect())).await?;
+
+    let mut results = Vec::with_capacity(total);
+    let mut items = Vec::new();
+    for (index, (provider, api_key)) in providers.iter().zip(api_keys).enumerate() {
+        match api_key {
+            Ok(api_key) => items.push((index, api_key)),
             Err(e) => {
-                log::warn!("查询密钥 {} 余额失败: {}", key, e);
+                log::warn!("解析密钥 {} 失败: {}", provider.name, e);
+                let result = Err(e);
+                on_result(provider, &result, total);
+                results.push((index, result));
             }
         }
-
-        // 延迟 200ms 避免请求过快
-        if i < api_keys.len() - 1 {
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        }
     }
+    let checked =
+        check_many_with_progress(&client, items, &config.balance_check, |index, result| {
+            on_result(&providers[*index], result, total)
+        })
+        .await;
+    results.extend(checked);
+    results.sort_by_key(|(index, _)| *index);
+
+    let recorded: Vec<(String, Result<BalanceInfo, AppError>)> = results
+        .iter()
+        .map(|(index, result)| (providers[*index].id.clone(), result.clone()))
+        .collect();
+    run_blocking(move || {
+        let recorded: Vec<(&str, &Result<BalanceInfo, AppError>)> = recorded
+            .iter()
+            .map(|(id, result)| (id.as_str(), result))
+            .collect();
+        record_balance_results(&recorded)
+    })
+    .await?;
 
-    Ok(results)
+    let mut providers: Vec<Option<Provider>> = providers.into_iter().map(Some).collect();
+    Ok(results
+        .into_iter()
+        .filter_map(|(index, result)| providers[index].take().map(|p| (p, result)))
+        .collect())
 }
@@ -0,0 +1,299 @@
+use crate::config::get_app_config_dir;
+use crate::error::AppError;
+use crate::models::{BalanceForecast, BalanceInfo, BalanceSnapshot, HistoryRange};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// 预测时最多参考最近多少天的快照
+const FORECAST_WINDOW_DAYS: i64 = 14;
+
+/// 获取余额历史路径 (~/.factory-ai-droid-switch/balance_history.jsonl)
+pub fn get_balance_history_path() -> PathBuf {
+    get_app_config_dir().join("balance_history.jsonl")
+}
+
+/// 追加一条余额快照；写入失败只记录日志，不影响余额更新
+pub fn append_snapshot(provider_id: &str, balance: &BalanceInfo) {
+    let snapshot = BalanceSnapshot {
+        timestamp: Utc::now().to_rfc3339(),
+        provider_id: provider_id.to_string(),
+        balance: balance.clone(),
+    };
+    if let Err(e) = try_append(&snapshot) {
+        log::warn!("写入余额历史失败: {}", e);
+    }
+}
+
+fn try_append(snapshot: &BalanceSnapshot) -> Result<(), AppError> {
+    let path = get_balance_history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
+    }
+
+    let mut line =
+        serde_json::to_string(snapshot).map_err(|e| AppError::parse("序列化余额快照失败", e))?;
+    line.push('\n');
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| AppError::io("打开余额历史失败", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| AppError::io("写入余额历史失败", e))
+}
+
+fn parse_time(value: &str) -> Result<DateTime<FixedOffset>, AppError> {
+    DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::invalid_input(format!("无效的时间 {}: {}", value, e)))
+}
+
+/// 读取密钥的余额历史（按时间正序）
+pub fn get_balance_history(
+    provider_id: &str,
+    range: &HistoryRange,
+) -> Result<Vec<BalanceSnapshot>, AppError> {
+    let path = get_balance_history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let since = range.since.as_deref().map(parse_time).transpose()?;
+    let until = range.until.as_deref().map(parse_time).transpose()?;
+
+    let file = fs::File::open(&path).map_err(|e| AppError::io("打开余额历史失败", e))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(
+            |line| match serde_json::from_str::<BalanceSnapshot>(&line) {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    log::warn!("跳过无法解析的余额快照: {}", e);
+                    None
+                }
+            },
+        )
+        .filter(|s| s.provider_id == provider_id)
+        .filter(|s| {
+            let Ok(time) = parse_time(&s.timestamp) else {
+                return false;
+            };
+            since.is_none_or(|t| time >= t) && until.is_none_or(|t| time <= t)
+        })
+        .collect())
+}
+
+/// 取最后一次额度重置（已用量下降或总额度变化）之后的快照
+fn current_period(history: &[BalanceSnapshot]) -> &[BalanceSnapshot] {
+    let start = history
+        .windows(2)
+        .rposition(|w| {
+            w[1].balance.used < w[0].balance.used
+                || w[1].balance.allowance != w[0].balance.allowance
+        })
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    &history[start..]
+}
+
+/// 最小二乘法拟合每日消耗量
+fn daily_burn_rate(samples: &[(DateTime<FixedOffset>, u64)]) -> Option<f64> {
+    let (first, _) = samples.first()?;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(t, used)| ((*t - *first).num_seconds() as f64 / 86_400.0, *used as f64))
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let var_x: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if points.len() < 2 || var_x <= f64::EPSILON {
+        return None;
+    }
+    let cov: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    Some(cov / var_x)
+}
+
+/// 根据余额历史预测每日消耗量和用完时间，并与到期时间比较
+pub fn forecast(provider_id: &str) -> Result<BalanceForecast, AppError> {
+    let since = Utc::now() - Duration::days(FORECAST_WINDOW_DAYS);
+    let history = get_balance_history(
+        provider_id,
+        &HistoryRange {
+            since: Some(since.to_rfc3339()),
+            until: None,
+        },
+    )?;
+    Ok(forecast_from_history(provider_id, &history))
+}
+
+/// 按消耗速度推算用完时间；溢出或晚于到期时间时返回空
+fn project_exhaustion(
+    last_time: DateTime<FixedOffset>,
+    remaining: u64,
+    rate: f64,
+    expiry: Option<DateTime<FixedOffset>>,
+) -> Option<DateTime<FixedOffset>> {
+    let seconds = remaining as f64 / rate * 86_400.0;
+    if !seconds.is_finite() || seconds >= i64::MAX as f64 {
+        return None;
+    }
+    let projected = last_time.checked_add_signed(Duration::try_seconds(seconds as i64)?)?;
+    match expiry {
+        Some(expiry) if projected > expiry => None,
+        _ => Some(projected),
+    }
+}
+
+fn forecast_from_history(provider_id: &str, history: &[BalanceSnapshot]) -> BalanceForecast {
+    let period = current_period(history);
+    let samples: Vec<(DateTime<FixedOffset>, u64)> = period
+        .iter()
+        .filter_map(|s| parse_time(&s.timestamp).ok().map(|t| (t, s.balance.used)))
+        .collect();
+
+    let latest = period.last();
+    let remaining = latest.map(|s| s.balance.remaining);
+    let expiry_date = latest.and_then(|s| s.balance.expiry_date.clone());
+    let expiry = expiry_date.as_deref().and_then(|d| parse_time(d).ok());
+    let rate = daily_burn_rate(&samples);
+
+    let projected = match (rate, latest, samples.last()) {
+        (Some(rate), Some(latest), Some((last_time, _))) if rate > 0.0 => {
+            project_exhaustion(*last_time, latest.balance.remaining, rate, expiry)
+        }
+        _ => None,
+    };
+
+    let exhausts_before_expiry = match (rate, projected, expiry) {
+        (_, Some(projected), Some(expiry)) => Some(projected < expiry),
+        // 没有消耗或到期前用不完
+        (Some(_), None, Some(_)) => Some(false),
+        _ => None,
+    };
+
+    BalanceForecast {
+        provider_id: provider_id.to_string(),
+        sample_count: samples.len(),
+        daily_burn_rate: rate.map(|r| r.max(0.0)),
+        remaining,
+        projected_exhaustion_date: projected.map(|t| t.to_rfc3339()),
+        expiry_date,
+        exhausts_before_expiry,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPIRY: &str = "2026-12-31T00:00:00+00:00";
+
+    fn snapshot(timestamp: &str, used: u64, allowance: u64) -> BalanceSnapshot {
+        BalanceSnapshot {
+            timestamp: timestamp.to_string(),
+            provider_id: "p1".to_string(),
+            balance: BalanceInfo {
+                used,
+                allowance,
+                remaining: allowance.saturating_sub(used),
+                overage: 0,
+                used_ratio: used as f64 / allowance as f64,
+                percent_used: used as f64 / allowance as f64 * 100.0,
+                exceeded: used > allowance,
+                expiry_date: Some(EXPIRY.to_string()),
+                period_start: None,
+                tiers: Vec::new(),
+                raw: None,
+            },
+        }
+    }
+
+    #[test]
+    fn flat_usage_never_exhausts() {
+        let history = [
+            snapshot("2026-10-01T00:00:00+00:00", 1000, 200_000_000),
+            snapshot("2026-10-05T00:00:00+00:00", 1000, 200_000_000),
+            snapshot("2026-10-10T00:00:00+00:00", 1000, 200_000_000),
+        ];
+        let forecast = forecast_from_history("p1", &history);
+        assert_eq!(forecast.sample_count, 3);
+        assert_eq!(forecast.daily_burn_rate, Some(0.0));
+        assert!(forecast.projected_exhaustion_date.is_none());
+        assert_eq!(forecast.exhausts_before_expiry, Some(false));
+    }
+
+    #[test]
+    fn near_zero_slope_does_not_overflow() {
+        let history = [
+            snapshot("2026-10-01T00:00:00+00:00", 1000, 200_000_000),
+            snapshot("2026-10-11T00:00:00+00:00", 1001, 200_000_000),
+        ];
+        let forecast = forecast_from_history("p1", &history);
+        assert!(forecast.daily_burn_rate.unwrap() > 0.0);
+        assert!(forecast.projected_exhaustion_date.is_none());
+        assert_eq!(forecast.exhausts_before_expiry, Some(false));
+    }
+
+    #[test]
+    fn slow_burn_past_expiry_is_not_projected() {
+        let history = [
+            snapshot("2026-10-01T00:00:00+00:00", 0, 200_000_000),
+            snapshot("2026-10-11T00:00:00+00:00", 10_000, 200_000_000),
+        ];
+        let forecast = forecast_from_history("p1", &history);
+        assert!(forecast.projected_exhaustion_date.is_none());
+        assert_eq!(forecast.exhausts_before_expiry, Some(false));
+    }
+
+    #[test]
+    fn single_sample_has_no_rate() {
+        let history = [snapshot("2026-10-01T00:00:00+00:00", 1000, 200_000_000)];
+        let forecast = forecast_from_history("p1", &history);
+        assert_eq!(forecast.sample_count, 1);
+        assert_eq!(forecast.remaining, Some(199_999_000));
+        assert!(forecast.daily_burn_rate.is_none());
+        assert!(forecast.projected_exhaustion_date.is_none());
+        assert!(forecast.exhausts_before_expiry.is_none());
+    }
+
+    #[test]
+    fn normal_burn_projects_exhaustion() {
+        // 每天消耗 1000 万，剩余 1 亿，10 天后用完
+        let history = [
+            snapshot("2026-10-01T00:00:00+00:00", 80_000_000, 200_000_000),
+            snapshot("2026-10-02T00:00:00+00:00", 90_000_000, 200_000_000),
+            snapshot("2026-10-03T00:00:00+00:00", 100_000_000, 200_000_000),
+        ];
+        let forecast = forecast_from_history("p1", &history);
+        let rate = forecast.daily_burn_rate.unwrap();
+        assert!((rate - 10_000_000.0).abs() < 1.0);
+        let projected = forecast.projected_exhaustion_date.unwrap();
+        assert_eq!(
+            parse_time(&projected).unwrap(),
+            parse_time("2026-10-13T00:00:00+00:00").unwrap()
+        );
+        assert_eq!(forecast.exhausts_before_expiry, Some(true));
+    }
+
+    #[test]
+    fn reset_starts_a_new_period() {
+        let history = [
+            snapshot("2026-10-01T00:00:00+00:00", 150_000_000, 200_000_000),
+            snapshot("2026-10-02T00:00:00+00:00", 0, 200_000_000),
+            snapshot("2026-10-03T00:00:00+00:00", 10_000_000, 200_000_000),
+        ];
+        let forecast = forecast_from_history("p1", &history);
+        assert_eq!(forecast.sample_count, 2);
+        let rate = forecast.daily_burn_rate.unwrap();
+        assert!((rate - 10_000_000.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,831 @@
+//! droid-switch 命令行工具
+//!
+//! 与桌面应用共用 droid_switch_lib 和同一份配置文件，适合在无图形界面的
+//! 服务器（如通过 SSH 登录的 Linux 机器）上管理密钥和模型。
+
+use clap::{Parser, Subcommand};
+use droid_switch_lib::audit::{self, AuditTarget};
+use droid_switch_lib::error::AppError;
+use droid_switch_lib::models::{
+    AuditAction, AuditFilter, AuditOutcome, AuditSource, BalanceInfo, ConnectivityStage,
+    ImportStatus, Provider, ProviderStatus, ReasoningLevel, TransferFormat,
+};
+use droid_switch_lib::{
+    balance, balance_history, droid_config, expiry, health, import_export, model_catalog,
+    model_manager, network, project, provider_manager, vault,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// 读取密钥库口令的环境变量
+const PASSPHRASE_ENV: &str = "DROID_SWITCH_PASSPHRASE";
+
+#[derive(Parser)]
+#[command(
+    name = "droid-switch",
+    version,
+    about = "Factory AI Droid 密钥与模型切换工具"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 列出所有密钥
+    List,
+    /// 添加密钥
+    Add {
+        /// 密钥名称
+        name: String,
+        /// API Key（fk- 开头）
+        api_key: String,
+        /// 添加前在线校验 API Key（查询一次余额）
+        #[arg(long)]
+        verify: bool,
+    },
+    /// 删除密钥
+    Remove {
+        /// 密钥名称或 ID
+        target: String,
+    },
+    /// 切换到指定密钥
+    Use {
+        /// 密钥名称或 ID
+        target: String,
+    },
+    /// 停用当前密钥
+    Off,
+    /// 输出 ~/.factory/config.json 中当前的 API Key（供 shell 包装函数调用）
+    CurrentKey,
+    /// 导出所有密钥（包含明文 API Key）
+    Export {
+        /// 导出格式（json / csv / dotenv），默认按输出文件扩展名判断
+        #[arg(long, value_parser = parse_transfer_format)]
+        format: Option<TransferFormat>,
+        /// 输出文件（默认输出到标准输出，此时格式默认为 json）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 从文件导入密钥
+    Import {
+        /// 导入文件
+        file: PathBuf,
+        /// 导入格式（json / csv / dotenv），默认按文件扩展名判断
+        #[arg(long, value_parser = parse_transfer_format)]
+        format: Option<TransferFormat>,
+    },
+    /// 查询余额（默认查询当前密钥）
+    Balance {
+        /// 查询所有密钥并更新到配置
+        #[arg(long)]
+        all: bool,
+        /// 密钥名称或 ID
+        target: Option<String>,
+    },
+    /// 重新查询所有密钥，检查是否无效、已吊销、已过期或已用尽
+    Validate,
+    /// 根据余额历史预测消耗速度和用完时间（默认所有密钥）
+    Forecast {
+        /// 密钥名称或 ID
+        target: Option<String>,
+    },
+    /// 按网络设置（代理、证书）测试能否连接 Factory API
+    Connectivity,
+    /// 列出可用模型
+    Models,
+    /// 模型管理
+    Model {
+        #[command(subcommand)]
+        command: ModelCommand,
+    },
+    /// 设置当前模型的推理级别（off / low / medium / high）
+    Reasoning {
+        #[arg(value_parser = parse_reasoning_level)]
+        level: ReasoningLevel,
+    },
+    /// 项目绑定（.droid-switch.toml）
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommand,
+    },
+    /// 查看审计日志（最新的在前）
+    Audit {
+        /// 操作类型（switchProvider / disableProvider / addProvider / removeProvider /
+        /// setSelectedModel / refreshBalance）
+        #[arg(long, value_parser = parse_audit_action)]
+        action: Option<AuditAction>,
+        /// 密钥名称或 ID
+        #[arg(long)]
+        provider: Option<String>,
+        /// 起始时间（RFC 3339）
+        #[arg(long)]
+        since: Option<String>,
+        /// 最多显示条数
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// 显示目录对应的项目绑定
+    Show {
+        /// 起始目录（默认当前目录）
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// 开始项目会话：按行输出运行 droid 所需的环境变量（NAME=VALUE，供 shell 包装函数调用）
+    Activate {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// 会话 ID（结束会话时传给 restore）
+        #[arg(long, default_value = "default")]
+        session: String,
+        /// 运行包装函数的 shell 进程 ID（进程退出后会话会被清理）
+        #[arg(long)]
+        pid: Option<u32>,
+    },
+    /// 结束项目会话（供 shell 包装函数调用；不指定会话时结束所有项目会话）
+    Restore {
+        #[arg(long)]
+        session: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelCommand {
+    /// 切换到指定模型
+    Use {
+        /// 模型 ID
+        model_id: String,
+    },
+    /// 查看或设置外部模型清单（本地文件路径或 HTTP(S) 地址）
+    Catalog {
+        /// 设置模型清单来源并立即获取
+        #[arg(long, conflicts_with = "clear")]
+        source: Option<String>,
+        /// 清除模型清单来源，恢复使用内置模型列表
+        #[arg(long)]
+        clear: bool,
+        /// 重新获取模型清单
+        #[arg(long)]
+        refresh: bool,
+    },
+}
+
+fn parse_reasoning_level(value: &str) -> Result<ReasoningLevel, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase())).map_err(|_| {
+        format!(
+            "无效的推理级别: {}（可选 off / low / medium / high）",
+            value
+        )
+    })
+}
+
+fn parse_transfer_format(value: &str) -> Result<TransferFormat, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map_err(|_| format!("无效的格式: {}（可选 json / csv / dotenv）", value))
+}
+
+fn parse_audit_action(value: &str) -> Result<AuditAction, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("无效的操作类型: {}", value))
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), AppError> {
+    match command {
+        Command::List => list_providers(),
+        Command::Add {
+            name,
+            api_key,
+            verify,
+        } => {
+            unlock_vault_if_needed()?;
+            let result = block_on(health::add_provider(name.clone(), api_key, verify));
+            audit::record(
+                AuditAction::AddProvider,
+                AuditSource::Cli,
+                AuditTarget::named(result.as_ref().ok().map(|p| p.id.clone()), name),
+                None,
+                &result,
+            );
+            let provider = result?;
+            println!("已添加密钥: {} ({})", provider.name, provider.id);
+            Ok(())
+        }
+        Command::Remove { target } => {
+            let config = provider_manager::load_config()?;
+            let provider = provider_manager::find_provider(&config, &target)?.clone();
+            let result = provider_manager::remove_provider(provider.id.clone());
+            audit::record(
+                AuditAction::RemoveProvider,
+                AuditSource::Cli,
+                AuditTarget::named(Some(provider.id), provider.name.clone()),
+                None,
+                &result,
+            );
+            result?;
+            println!("已删除密钥: {}", provider.name);
+            Ok(())
+        }
+        Command::Use { target } => {
+            unlock_vault_if_needed()?;
+            let config = provider_manager::load_config()?;
+            let id = provider_manager::find_provider(&config, &target)?
+                .id
+                .clone();
+            let result = provider_manager::switch_provider(id.clone());
+            audit::record(
+                AuditAction::SwitchProvider,
+                AuditSource::Cli,
+                AuditTarget::provider(&id),
+                None,
+                &result,
+            );
+            let provider = result?;
+            println!("已切换到密钥: {}", provider.name);
+            Ok(())
+        }
+        Command::Off => {
+            let target = AuditTarget::active_provider();
+            let result = provider_manager::disable_provider();
+            audit::record(
+                AuditAction::DisableProvider,
+                AuditSource::Cli,
+                target,
+                None,
+                &result,
+            );
+            result?;
+            println!("已停用当前密钥");
+            Ok(())
+        }
+        Command::CurrentKey => {
+            if let Some(api_key) = droid_config::get_factory_api_key_env()? {
+                println!("{}", api_key);
+            }
+            Ok(())
+        }
+        Command::Export { format, output } => {
+            unlock_vault_if_needed()?;
+            match output {
+                Some(path) => {
+                    let count = import_export::export_providers_to_file(format, &path)?;
+                    eprintln!("已导出 {} 个密钥到 {}", count, path.display());
+                }
+                None => print!(
+                    "{}",
+                    import_export::export_providers(format.unwrap_or(TransferFormat::Json))?
+                ),
+            }
+            Ok(())
+        }
+        Command::Import { file, format } => {
+            unlock_vault_if_needed()?;
+            let report = import_export::import_providers_from_file(format, &file)?;
+            audit::record_import(AuditSource::Cli, &report);
+            for row in &report.rows {
+                let status = match row.status {
+                    ImportStatus::Added => "已添加",
+                    ImportStatus::SkippedDuplicate => "已跳过",
+                    ImportStatus::Invalid => "无效",
+                };
+                match &row.message {
+                    Some(message) => println!(
+                        "第 {} 行 {:<24} {}: {}",
+                        row.line, row.name, status, message
+                    ),
+                    None => println!("第 {} 行 {:<24} {}", row.line, row.name, status),
+                }
+            }
+            println!(
+                "新增 {}，跳过 {}，无效 {}",
+                report.added, report.skipped, report.invalid
+            );
+            Ok(())
+        }
+        Command::Balance { all, target } => {
+            unlock_vault_if_needed()?;
+            block_on(show_balance(all, target))
+        }
+        Command::Validate => {
+            unlock_vault_if_needed()?;
+            block_on(validate_providers())
+        }
+        Command::Forecast { target } => show_forecast(target),
+        Command::Connectivity => block_on(test_connectivity()),
+        Command::Models => list_models(),
+        Command::Model {
+            command: ModelCommand::Use { model_id },
+        } => {
+            let result = model_manager::set_selected_model(model_id.clone());
+            audit::record(
+                AuditAction::SetSelectedModel,
+                AuditSource::Cli,
+                AuditTarget::model(&model_id),
+                None,
+                &result,
+            );
+            result?;
+            println!("已切换到模型: {}", model_id);
+            Ok(())
+        }
+        Command::Model {
+            command:
+                ModelCommand::Catalog {
+                    source,
+                    clear,
+                    refresh,
+                },
+        } => block_on(model_catalog_command(source, clear, refresh)),
+        Command::Reasoning { level } => {
+            let model_id = model_manager::get_selected_model()?
+                .ok_or_else(|| AppError::invalid_input("当前没有选中的模型"))?;
+            model_manager::set_model_reasoning_level(model_id.clone(), level.clone())?;
+            println!("模型 {} 的推理级别已设置为: {:?}", model_id, level);
+            Ok(())
+        }
+        Command::Project { command } => run_project(command),
+        Command::Audit {
+            action,
+            provider,
+            since,
+            limit,
+        } => show_audit_log(AuditFilter {
+            action,
+            provider,
+            since,
+            limit: Some(limit),
+            ..AuditFilter::default()
+        }),
+    }
+}
+
+fn current_dir_or(dir: Option<PathBuf>) -> Result<PathBuf, AppError> {
+    match dir {
+        Some(dir) => Ok(dir),
+        None => std::env::current_dir().map_err(|e| AppError::io("获取当前目录失败", e)),
+    }
+}
+
+fn run_project(command: ProjectCommand) -> Result<(), AppError> {
+    match command {
+        ProjectCommand::Show { dir } => {
+            let dir = current_dir_or(dir)?;
+            let Some(binding) = project::resolve(&dir)? else {
+                println!("未找到 {}，使用全局设置", project::PROJECT_FILE_NAME);
+                return Ok(());
+            };
+            println!("项目: {}", binding.project_dir);
+            println!(
+                "密钥: {}",
+                binding
+                    .provider_name
+                    .as_deref()
+                    .unwrap_or(&binding.provider)
+            );
+            if let Some(model) = &binding.model {
+                println!("模型: {}", model);
+            }
+            if let Some(reasoning) = &binding.reasoning {
+                println!("推理级别: {:?}", reasoning);
+            }
+            if let Some(e) = binding.error {
+                return Err(AppError::other(e));
+            }
+            Ok(())
+        }
+        ProjectCommand::Activate { dir, session, pid } => {
+            let dir = current_dir_or(dir)?;
+            unlock_vault_if_needed()?;
+            let (binding, api_key) = project::resolve_api_key_for_dir(&dir)?
+                .ok_or_else(|| AppError::not_found("项目绑定文件", project::PROJECT_FILE_NAME))?;
+            let session = model_manager::begin_project_session(
+                &session,
+                &binding.project_dir,
+                pid,
+                binding.model.as_deref(),
+                binding.reasoning.clone(),
+            )?;
+            println!("{}={}", droid_config::API_KEY_ENV, api_key);
+            if let Some(model_id) = &session.model_id {
+                println!("{}={}", droid_config::MODEL_ENV, model_id);
+            }
+            if let Some(level) = &session.reasoning_level {
+                println!(
+                    "{}={}",
+                    droid_config::REASONING_EFFORT_ENV,
+                    model_manager::reasoning_level_to_effort(level)
+                );
+            }
+            Ok(())
+        }
+        ProjectCommand::Restore { session } => {
+            model_manager::end_project_session(session.as_deref())
+        }
+    }
+}
+
+/// 在单线程 tokio 运行时中执行异步任务（余额查询需要）
+fn block_on<T, F: std::future::Future<Output = Result<T, AppError>>>(
+    future: F,
+) -> Result<T, AppError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| AppError::other(format!("创建异步运行时失败: {}", e)))?
+        .block_on(future)
+}
+
+/// 密钥库已启用且未解锁时，从环境变量或终端读取口令解锁
+fn unlock_vault_if_needed() -> Result<(), AppError> {
+    let config = provider_manager::load_config()?;
+    if vault::ensure_unlocked(&config).is_ok() {
+        return Ok(());
+    }
+
+    let passphrase = match std::env::var(PASSPHRASE_ENV) {
+        Ok(p) if !p.is_empty() => p,
+        _ => rpassword::prompt_password("密钥库口令: ")
+            .map_err(|e| AppError::io("读取口令失败", e))?,
+    };
+    provider_manager::unlock_vault(passphrase)
+}
+
+async fn test_connectivity() -> Result<(), AppError> {
+    let report = network::test_connectivity().await?;
+    println!("地址: {}", report.base_url);
+    if let Some(proxy) = &report.proxy {
+        println!("代理: {}", proxy);
+    }
+    if report.ok {
+        println!(
+            "连接成功（HTTP {}，{} 毫秒）",
+            report.status.unwrap_or_default(),
+            report.elapsed_ms
+        );
+        return Ok(());
+    }
+
+    let stage = match report.failed_stage {
+        Some(ConnectivityStage::Config) => "网络设置无效",
+        Some(ConnectivityStage::Dns) => "域名解析失败",
+        Some(ConnectivityStage::Proxy) => "代理连接失败",
+        Some(ConnectivityStage::Tls) => "TLS 握手或证书校验失败",
+        Some(ConnectivityStage::Timeout) => "请求超时",
+        Some(ConnectivityStage::Connect) | None => "无法建立连接",
+    };
+    let message = format!("{}: {}", stage, report.error.unwrap_or_default());
+    Err(match report.failed_stage {
+        Some(ConnectivityStage::Config) => AppError::invalid_input(message),
+        Some(ConnectivityStage::Timeout) => AppError::Timeout(message),
+        _ => AppError::Network(message),
+    })
+}
+
+fn list_providers() -> Result<(), AppError> {
+    let config = provider_manager::load_config()?;
+    if config.providers.is_empty() {
+        println!("(无密钥)");
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now();
+    for provider in &config.providers {
+        let marker = if config.active_provider_id.as_ref() == Some(&provider.id) {
+            "*"
+        } else {
+            " "
+        };
+        let mut balance = provider
+            .balance
+            .as_ref()
+            .map(format_balance)
+            .unwrap_or_else(|| "余额未知".to_string());
+        let hints: Vec<String> = provider
+            .balance
+            .as_ref()
+            .and_then(|info| model_manager::effective_remaining_hint(&config, info))
+            .into_iter()
+            .chain(balance::format_age(provider, now).map(|age| {
+                if provider.balance_stale {
+                    format!("{}查询，已过时", age)
+                } else {
+                    format!("{}查询", age)
+                }
+            }))
+            .chain(
+                health::status_hint(provider.status)
+                    .filter(|_| !provider.expired)
+                    .map(str::to_string),
+            )
+            .chain(expiry::expiry_hint(provider, now))
+            .collect();
+        if !hints.is_empty() {
+            balance.push_str(&format!("（{}）", hints.join("，")));
+        }
+        println!(
+            "{} {:<24} {}  {}",
+            marker, provider.name, provider.id, balance
+        );
+        if let Some(error) = &provider.last_error {
+            println!(
+                "  {:<24} 连续 {} 次刷新失败: {}",
+                "", provider.consecutive_failures, error
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn show_balance(all: bool, target: Option<String>) -> Result<(), AppError> {
+    let config = provider_manager::load_config()?;
+
+    let ids: Vec<String> = if all {
+        config.providers.iter().map(|p| p.id.clone()).collect()
+    } else if let Some(target) = target {
+        vec![provider_manager::find_provider(&config, &target)?
+            .id
+            .clone()]
+    } else {
+        vec![
+            provider_manager::get_active_provider()?
+                .ok_or_else(|| {
+                    AppError::invalid_input("当前没有激活的密钥，请指定密钥或使用 --all")
+                })?
+                .id,
+        ]
+    };
+
+    // 并发查询，最后统一写入配置
+    let mut failed = Vec::new();
+    for (provider, result) in
+        balance::refresh_providers(|p| ids.contains(&p.id), |_, _, _| {}).await?
+    {
+        audit::record_balance(
+            AuditSource::Cli,
+            AuditTarget::named(Some(provider.id.clone()), provider.name.clone()),
+            &result,
+        );
+        match result {
+            Ok(info) => {
+                println!("{:<24} {}", provider.name, format_balance(&info));
+                if let Some(hint) = model_manager::effective_remaining_hint(&config, &info) {
+                    println!("{:<24}   {}", "", hint);
+                }
+                for line in format_other_tiers(&info) {
+                    println!("{:<24}   {}", "", line);
+                }
+            }
+            Err(e) => {
+                println!("{:<24} 查询失败: {}", provider.name, e);
+                // 离线时仍展示上次查询到的余额
+                if let (Some(info), Some(age)) = (
+                    &provider.balance,
+                    balance::format_age(&provider, chrono::Utc::now()),
+                ) {
+                    println!("{:<24}   上次余额（{}）: {}", "", age, format_balance(info));
+                }
+                failed.push(e);
+            }
+        }
+    }
+
+    // 标记已过期的密钥并输出新的到期提醒
+    for alert in expiry::check_expiry()? {
+        println!("提醒: {}", alert.message);
+    }
+
+    // 只有一个密钥失败时保留其错误类型，便于脚本按退出码判断
+    match failed.len() {
+        0 => Ok(()),
+        1 => Err(failed.remove(0)),
+        n => Err(AppError::other(format!("{} 个密钥余额查询失败", n))),
+    }
+}
+
+async fn validate_providers() -> Result<(), AppError> {
+    let validations = health::validate_all_providers(AuditSource::Cli, |_, _, _| {}).await?;
+    if validations.is_empty() {
+        println!("(无密钥)");
+        return Ok(());
+    }
+
+    let mut rejected = 0;
+    let mut failed = Vec::new();
+    for validation in validations {
+        let status = match validation.status {
+            ProviderStatus::Unknown => "未知",
+            ProviderStatus::Valid => "正常",
+            ProviderStatus::Invalid => "无效",
+            ProviderStatus::Revoked => "已吊销",
+            ProviderStatus::Expired => "已过期",
+            ProviderStatus::Exhausted => "已用尽",
+        };
+        match &validation.error {
+            Some(e) => println!("{:<24} {}: {}", validation.provider_name, status, e),
+            None => println!("{:<24} {}", validation.provider_name, status),
+        }
+        if matches!(
+            validation.status,
+            ProviderStatus::Invalid | ProviderStatus::Revoked
+        ) {
+            rejected += 1;
+        } else if let Some(e) = validation.error {
+            failed.push(e);
+        }
+    }
+
+    // 有密钥被拒绝时以 API Key 无效退出，其余失败按余额查询的规则处理
+    if rejected > 0 {
+        return Err(AppError::InvalidKey(format!(
+            "{} 个密钥无效或已吊销",
+            rejected
+        )));
+    }
+    match failed.len() {
+        0 => Ok(()),
+        1 => Err(failed.remove(0)),
+        n => Err(AppError::other(format!("{} 个密钥校验失败", n))),
+    }
+}
+
+fn show_audit_log(filter: AuditFilter) -> Result<(), AppError> {
+    let records = audit::get_audit_log(&filter)?;
+    if records.is_empty() {
+        println!("(无记录)");
+        return Ok(());
+    }
+
+    for record in &records {
+        let target = record
+            .provider_name
+            .as_deref()
+            .or(record.provider_id.as_deref())
+            .or(record.model_id.as_deref())
+            .unwrap_or("-");
+        let outcome = match record.outcome {
+            AuditOutcome::Success => "成功".to_string(),
+            AuditOutcome::Failure => {
+                format!("失败: {}", record.error.as_deref().unwrap_or_default())
+            }
+        };
+        let source = match record.source {
+            AuditSource::Tray => "托盘",
+            AuditSource::Ui => "界面",
+            AuditSource::Cli => "命令行",
+            AuditSource::Auto => "自动",
+        };
+        println!(
+            "{}  {:<6} {:<18} {:<24} {}{}",
+            &record.timestamp[..record.timestamp.len().min(19)],
+            source,
+            format!("{:?}", record.action),
+            target,
+            outcome,
+            record
+                .detail
+                .as_ref()
+                .map(|d| format!("  ({})", d))
+                .unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn show_forecast(target: Option<String>) -> Result<(), AppError> {
+    let config = provider_manager::load_config()?;
+    let providers: Vec<&Provider> = match &target {
+        Some(target) => vec![provider_manager::find_provider(&config, target)?],
+        None => config.providers.iter().collect(),
+    };
+
+    for provider in providers {
+        let forecast = balance_history::forecast(&provider.id)?;
+        let Some(rate) = forecast.daily_burn_rate else {
+            println!(
+                "{:<24} 历史数据不足（{} 条快照）",
+                provider.name, forecast.sample_count
+            );
+            continue;
+        };
+
+        let mut text = format!("每日消耗 {}", balance::format_tokens(rate.round() as u64));
+        match &forecast.projected_exhaustion_date {
+            Some(date) => text.push_str(&format!("  预计用完: {}", &date[..date.len().min(10)])),
+            None => text.push_str("  预计不会用完"),
+        }
+        if let Some(expiry) = &forecast.expiry_date {
+            text.push_str(&format!("  到期: {}", &expiry[..expiry.len().min(10)]));
+        }
+        if forecast.exhausts_before_expiry == Some(true) {
+            text.push_str(" [到期前用完]");
+        }
+        println!("{:<24} {}", provider.name, text);
+    }
+    Ok(())
+}
+
+fn list_models() -> Result<(), AppError> {
+    let models = model_manager::get_available_models()?;
+    let selected = model_manager::get_selected_model()?;
+
+    for model in &models {
+        let marker = if selected.as_ref() == Some(&model.id) {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{} {:<32} {:<24} {:<10} {:?}{}",
+            marker,
+            model.id,
+            model.name,
+            model.provider,
+            model.reasoning_level,
+            if model.deprecated {
+                "（已弃用）"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+async fn model_catalog_command(
+    source: Option<String>,
+    clear: bool,
+    refresh: bool,
+) -> Result<(), AppError> {
+    let status = if source.is_some() || clear {
+        let mut settings = model_catalog::get_settings()?;
+        settings.source = source;
+        model_catalog::set_settings(settings)?;
+        model_catalog::refresh_catalog().await?
+    } else if refresh {
+        model_catalog::refresh_catalog().await?
+    } else {
+        model_catalog::get_status()?
+    };
+
+    println!("来源: {}", status.source.as_deref().unwrap_or("(未设置)"));
+    match (status.fallback, status.version) {
+        (false, Some(version)) => println!(
+            "使用清单版本 {}，共 {} 个模型，获取于 {}",
+            version,
+            status.model_count,
+            status.fetched_at.as_deref().unwrap_or_default()
+        ),
+        _ => println!("使用内置模型列表，共 {} 个模型", status.model_count),
+    }
+    Ok(())
+}
+
+fn format_balance(info: &BalanceInfo) -> String {
+    let mut text = format!(
+        "剩余 {} / {} ({:.1}% 已用)",
+        balance::format_tokens(info.remaining),
+        balance::format_tokens(info.allowance),
+        info.percent_used
+    );
+    if info.exceeded {
+        text.push_str(" [已超额]");
+    }
+    if let Some(expiry) = &info.expiry_date {
+        text.push_str(&format!(" 到期: {}", &expiry[..expiry.len().min(10)]));
+    }
+    text
+}
+
+/// standard 以外的档位用量，每个档位一行
+fn format_other_tiers(info: &BalanceInfo) -> Vec<String> {
+    info.tiers
+        .iter()
+        .filter(|tier| tier.name != "standard")
+        .map(|tier| {
+            format!(
+                "{}: 剩余 {} / {} ({:.1}% 已用)",
+                tier.name,
+                balance::format_tokens(tier.remaining),
+                balance::format_tokens(tier.allowance),
+                tier.used_ratio * 100.0
+            )
+        })
+        .collect()
+}
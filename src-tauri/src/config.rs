@@ -1,6 +1,10 @@
+use crate::error::AppError;
+use fs4::fs_std::FileExt;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fs;
 use std::io::Write;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 
 /// 获取应用配置目录路径 (~/.factory-ai-droid-switch)
@@ -15,41 +19,118 @@ pub fn get_app_config_path() -> PathBuf {
     get_app_config_dir().join("config.json")
 }
 
+/// 获取配置锁文件路径
+pub fn get_app_config_lock_path() -> PathBuf {
+    get_app_config_dir().join("config.json.lock")
+}
+
+thread_local! {
+    /// 当前线程持有配置锁的层数
+    static LOCK_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// 配置文件锁
+///
+/// 基于锁文件的跨进程咨询锁，桌面应用和命令行同时运行时互斥。
+/// 同一线程内可重入；析构时释放。不能跨线程传递，也不要跨 await 持有。
+pub struct ConfigLock {
+    file: Option<fs::File>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        LOCK_DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+        if let Some(file) = &self.file {
+            if let Err(e) = FileExt::unlock(file) {
+                log::warn!("释放配置锁失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 获取配置文件锁（阻塞等待其他进程释放）
+pub fn lock_app_config() -> Result<ConfigLock, AppError> {
+    let depth = LOCK_DEPTH.with(Cell::get);
+    if depth > 0 {
+        LOCK_DEPTH.with(|d| d.set(depth + 1));
+        return Ok(ConfigLock {
+            file: None,
+            _not_send: PhantomData,
+        });
+    }
+
+    let path = get_app_config_lock_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
+    }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .map_err(|e| AppError::io("打开配置锁文件失败", e))?;
+    FileExt::lock_exclusive(&file).map_err(|e| AppError::io("获取配置锁失败", e))?;
+
+    LOCK_DEPTH.with(|d| d.set(1));
+    Ok(ConfigLock {
+        file: Some(file),
+        _not_send: PhantomData,
+    })
+}
+
+/// 在阻塞线程池中执行同步的配置读写
+///
+/// 配置锁会阻塞等待其他进程释放，配置文件和系统钥匙串访问也是同步 I/O，
+/// 异步函数中通过此函数执行，避免占用运行时的工作线程。
+pub async fn run_blocking<T, F>(f: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::other(format!("后台任务执行失败: {}", e)))?
+}
+
 /// 读取 JSON 配置文件
-pub fn read_json_file<T: for<'a> Deserialize<'a>>(path: &Path) -> Result<T, String> {
+pub fn read_json_file<T: for<'a> Deserialize<'a>>(path: &Path) -> Result<T, AppError> {
     if !path.exists() {
-        return Err(format!("文件不存在: {}", path.display()));
+        return Err(AppError::not_found("文件", path.display().to_string()));
     }
 
-    let content = fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let content = fs::read_to_string(path).map_err(|e| AppError::io("读取文件失败", e))?;
 
-    serde_json::from_str(&content).map_err(|e| format!("解析 JSON 失败: {}", e))
+    serde_json::from_str(&content).map_err(|e| AppError::parse("解析 JSON 失败", e))
 }
 
 /// 写入 JSON 配置文件
-pub fn write_json_file<T: Serialize>(path: &Path, data: &T) -> Result<(), String> {
+pub fn write_json_file<T: Serialize>(path: &Path, data: &T) -> Result<(), AppError> {
     // 确保目录存在
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
     }
 
     let json =
-        serde_json::to_string_pretty(data).map_err(|e| format!("序列化 JSON 失败: {}", e))?;
+        serde_json::to_string_pretty(data).map_err(|e| AppError::parse("序列化 JSON 失败", e))?;
 
     atomic_write(path, json.as_bytes())
 }
 
 /// 原子写入：写入临时文件后 rename 替换，避免半写状态
-pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
+pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), AppError> {
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        fs::create_dir_all(parent).map_err(|e| AppError::io("创建目录失败", e))?;
     }
 
-    let parent = path.parent().ok_or_else(|| "无效的路径".to_string())?;
+    let parent = path
+        .parent()
+        .ok_or_else(|| AppError::invalid_input("无效的路径"))?;
     let mut tmp = parent.to_path_buf();
     let file_name = path
         .file_name()
-        .ok_or_else(|| "无效的文件名".to_string())?
+        .ok_or_else(|| AppError::invalid_input("无效的文件名"))?
         .to_string_lossy()
         .to_string();
     let ts = std::time::SystemTime::now()
@@ -59,10 +140,10 @@ pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
     tmp.push(format!("{}.tmp.{}", file_name, ts));
 
     {
-        let mut f = fs::File::create(&tmp).map_err(|e| format!("创建临时文件失败: {}", e))?;
+        let mut f = fs::File::create(&tmp).map_err(|e| AppError::io("创建临时文件失败", e))?;
         f.write_all(data)
-            .map_err(|e| format!("写入临时文件失败: {}", e))?;
-        f.flush().map_err(|e| format!("刷新临时文件失败: {}", e))?;
+            .map_err(|e| AppError::io("写入临时文件失败", e))?;
+        f.flush().map_err(|e| AppError::io("刷新临时文件失败", e))?;
     }
 
     #[cfg(unix)]
@@ -80,26 +161,26 @@ pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
         if path.exists() {
             let _ = fs::remove_file(path);
         }
-        fs::rename(&tmp, path).map_err(|e| format!("原子替换失败: {}", e))?;
+        fs::rename(&tmp, path).map_err(|e| AppError::io("原子替换失败", e))?;
     }
 
     #[cfg(not(windows))]
     {
-        fs::rename(&tmp, path).map_err(|e| format!("原子替换失败: {}", e))?;
+        fs::rename(&tmp, path).map_err(|e| AppError::io("原子替换失败", e))?;
     }
     Ok(())
 }
 
 /// 复制文件
-pub fn copy_file(from: &Path, to: &Path) -> Result<(), String> {
-    fs::copy(from, to).map_err(|e| format!("复制文件失败: {}", e))?;
+pub fn copy_file(from: &Path, to: &Path) -> Result<(), AppError> {
+    fs::copy(from, to).map_err(|e| AppError::io("复制文件失败", e))?;
     Ok(())
 }
 
 /// 删除文件
-pub fn delete_file(path: &Path) -> Result<(), String> {
+pub fn delete_file(path: &Path) -> Result<(), AppError> {
     if path.exists() {
-        fs::remove_file(path).map_err(|e| format!("删除文件失败: {}", e))?;
+        fs::remove_file(path).map_err(|e| AppError::io("删除文件失败", e))?;
     }
     Ok(())
 }
@@ -1,36 +1,20 @@
-use serde::Deserialize;
-use serde_json::Value;
-use crate::models::BalanceInfo;
-
-/// Factory.ai API 响应结构
-#[derive(Debug, Deserialize)]
-struct FactoryApiResponse {
-    usage: Usage,
-}
+use crate::config::atomic_write;
+use crate::error::AppError;
+use crate::jsonc;
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct Usage {
-    standard: StandardUsage,
-    #[serde(rename = "endDate")]
-    end_date: Option<i64>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct StandardUsage {
-    user_tokens: u64,
-    total_allowance: u64,
-    org_overage_used: u64,
-    used_ratio: f64,
-}
+/// droid 读取 API Key 的环境变量
+pub const API_KEY_ENV: &str = "FACTORY_API_KEY";
+/// 项目会话传给 droid 的模型环境变量（只作用于本次运行，不修改 settings.json）
+pub const MODEL_ENV: &str = "FACTORY_MODEL";
+/// 项目会话传给 droid 的推理级别环境变量
+pub const REASONING_EFFORT_ENV: &str = "FACTORY_REASONING_EFFORT";
 
 /// 设置 Factory API Key（写入 ~/.factory/config.json）
 /// 
 /// 更新配置文件并安装 shell 包装函数。
 /// shell 函数会在每次执行 droid 时从配置文件读取 api_key。
 /// 这样切换 key 后只需重启 droid 即可生效，无需重启终端。
-pub fn set_factory_api_key_env(api_key: &str) -> Result<(), String> {
+pub fn set_factory_api_key_env(api_key: &str) -> Result<(), AppError> {
     // 写入配置文件
     set_factory_config_api_key(api_key)?;
 
@@ -52,7 +36,7 @@ pub fn set_factory_api_key_env(api_key: &str) -> Result<(), String> {
 
 /// Windows: 设置用户级环境变量（写入注册表）
 #[cfg(target_os = "windows")]
-fn set_registry_env_var(name: &str, value: &str) -> Result<(), String> {
+fn set_registry_env_var(name: &str, value: &str) -> Result<(), AppError> {
     use std::process::Command;
     
     let set_cmd = if value.is_empty() {
@@ -70,11 +54,11 @@ fn set_registry_env_var(name: &str, value: &str) -> Result<(), String> {
     let output = Command::new("powershell")
         .args(["-NoProfile", "-Command", &set_cmd])
         .output()
-        .map_err(|e| format!("执行 PowerShell 失败: {}", e))?;
+        .map_err(|e| AppError::other(format!("执行 PowerShell 失败: {}", e)))?;
     
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("设置环境变量失败: {}", stderr));
+        return Err(AppError::other(format!("设置环境变量失败: {}", stderr)));
     }
     
     // 广播变更
@@ -86,7 +70,7 @@ fn set_registry_env_var(name: &str, value: &str) -> Result<(), String> {
 /// 清除 Factory API Key（从 ~/.factory/config.json 移除）
 /// 
 /// 同时会清除 Windows 注册表中的环境变量（如果存在）
-pub fn clear_factory_api_key_env() -> Result<(), String> {
+pub fn clear_factory_api_key_env() -> Result<(), AppError> {
     clear_factory_config_api_key()?;
 
     #[cfg(target_os = "windows")]
@@ -102,71 +86,51 @@ pub fn clear_factory_api_key_env() -> Result<(), String> {
 }
 
 /// 获取 ~/.factory/config.json 的路径
-fn get_factory_config_path() -> Result<std::path::PathBuf, String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+fn get_factory_config_path() -> Result<std::path::PathBuf, AppError> {
+    let home = dirs::home_dir().ok_or_else(|| AppError::other("无法获取用户主目录"))?;
     Ok(home.join(".factory").join("config.json"))
 }
 
-/// 设置 ~/.factory/config.json 中的 api_key
-fn set_factory_config_api_key(api_key: &str) -> Result<(), String> {
+/// 读取 ~/.factory/config.json 的原文，文件不存在时为空
+fn read_factory_config() -> Result<Option<String>, AppError> {
     let config_path = get_factory_config_path()?;
-    
-    // 确保 .factory 目录存在
-    if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("创建 .factory 目录失败: {}", e))?;
+    if !config_path.exists() {
+        return Ok(None);
     }
+    std::fs::read_to_string(&config_path)
+        .map(Some)
+        .map_err(|e| AppError::io("读取 config.json 失败", e))
+}
 
-    // 读取现有配置或创建新配置
-    let mut config: Value = if config_path.exists() {
-        let content = std::fs::read_to_string(&config_path)
-            .map_err(|e| format!("读取 config.json 失败: {}", e))?;
-        serde_json::from_str(&content).unwrap_or_else(|_| serde_json::json!({}))
-    } else {
-        serde_json::json!({})
+/// 设置 ~/.factory/config.json 中的 api_key
+///
+/// 只替换 api_key 的值，保留其他设置和注释；文件无法解析时返回错误，不修改文件。
+fn set_factory_config_api_key(api_key: &str) -> Result<(), AppError> {
+    let content = match read_factory_config()? {
+        Some(content) => jsonc::set_value(&content, "api_key", &serde_json::json!(api_key))
+            .map_err(|e| AppError::parse("解析 config.json 失败，未修改文件", e))?,
+        None => serde_json::to_string_pretty(&serde_json::json!({ "api_key": api_key }))
+            .map_err(|e| AppError::parse("序列化 config.json 失败", e))?,
     };
-
-    // 更新 api_key 字段
-    if let Some(obj) = config.as_object_mut() {
-        obj.insert("api_key".to_string(), serde_json::json!(api_key));
-    }
-
-    // 写入配置文件
-    let content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("序列化 config.json 失败: {}", e))?;
-    std::fs::write(&config_path, content)
-        .map_err(|e| format!("写入 config.json 失败: {}", e))?;
+    atomic_write(&get_factory_config_path()?, content.as_bytes())?;
 
     log::info!("已更新 ~/.factory/config.json 中的 api_key");
     Ok(())
 }
 
-/// 清除 ~/.factory/config.json 中的 api_key
-fn clear_factory_config_api_key() -> Result<(), String> {
-    let config_path = get_factory_config_path()?;
-    
-    if !config_path.exists() {
+/// 清除 ~/.factory/config.json 中的 api_key（文件无法解析时返回错误，不修改文件）
+fn clear_factory_config_api_key() -> Result<(), AppError> {
+    let Some(content) = read_factory_config()? else {
         log::info!("~/.factory/config.json 不存在，无需清除");
         return Ok(());
-    }
-
-    // 读取现有配置
-    let content = std::fs::read_to_string(&config_path)
-        .map_err(|e| format!("读取 config.json 失败: {}", e))?;
-    let mut config: Value = serde_json::from_str(&content)
-        .unwrap_or_else(|_| serde_json::json!({}));
+    };
 
-    // 移除 api_key 字段
-    if let Some(obj) = config.as_object_mut() {
-        obj.remove("api_key");
+    let updated = jsonc::remove_value(&content, "api_key")
+        .map_err(|e| AppError::parse("解析 config.json 失败，未修改文件", e))?;
+    if updated != content {
+        atomic_write(&get_factory_config_path()?, updated.as_bytes())?;
     }
 
-    // 写入配置文件
-    let content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("序列化 config.json 失败: {}", e))?;
-    std::fs::write(&config_path, content)
-        .map_err(|e| format!("写入 config.json 失败: {}", e))?;
-
     log::info!("已清除 ~/.factory/config.json 中的 api_key");
     Ok(())
 }
@@ -196,7 +160,7 @@ fn remove_wrapper_block(content: &str, marker_start: &str, marker_end: &str) ->
 /// 安装 shell 包装函数
 /// 
 /// 在 shell 配置文件中添加 droid 函数，每次执行时自动从 config.json 读取 api_key
-fn install_shell_wrapper() -> Result<(), String> {
+fn install_shell_wrapper() -> Result<(), AppError> {
     #[cfg(target_os = "windows")]
     {
         install_windows_wrapper()
@@ -210,17 +174,53 @@ fn install_shell_wrapper() -> Result<(), String> {
 
 /// Unix: 安装 shell 包装函数到 .zshrc 和 .bashrc
 #[cfg(not(target_os = "windows"))]
-fn install_unix_wrapper() -> Result<(), String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-    
+fn install_unix_wrapper() -> Result<(), AppError> {
+    let home = dirs::home_dir().ok_or_else(|| AppError::other("无法获取用户主目录"))?;
+    
+    // 从当前目录向上查找 .droid-switch.toml，找到时通过 droid-switch 命令行开始项目会话，
+    // 解析失败直接拒绝运行，避免项目工作误用全局密钥。项目的密钥、模型和推理级别以环境变量
+    // 只传给本次运行的 droid，不修改全局 settings.json。Ctrl-C、关闭终端时由 trap 结束会话，
+    // shell 被强制结束时由命令行按进程 ID 清理。
+    // 全局密钥优先通过命令行读取（支持注释等 JSONC 语法），未安装命令行时跳过注释行查找
     let shell_function = r#"
 # factory-ai-droid-switch Wrapper Start
-# Version: 2
+# Version: 6
 droid() {
     local api_key=""
     local config_file="$HOME/.factory/config.json"
-    if [ -f "$config_file" ]; then
-        api_key=$(grep -o '"api_key"[[:space:]]*:[[:space:]]*"[^"]*"' "$config_file" 2>/dev/null | sed 's/.*"api_key"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/')
+    local dir="$PWD"
+    local project_file=""
+    while :; do
+        if [ -f "$dir/.droid-switch.toml" ]; then
+            project_file="$dir/.droid-switch.toml"
+            break
+        fi
+        case "$dir" in /|"") break ;; esac
+        dir=$(dirname "$dir")
+    done
+    if [ -n "$project_file" ]; then
+        if ! command -v droid-switch >/dev/null 2>&1; then
+            echo "droid: found $project_file but the droid-switch CLI is not installed; refusing to fall back to the global key" >&2
+            return 1
+        fi
+        local session="$$-$RANDOM$RANDOM"
+        local activation line
+        local -a project_env
+        activation=$(command droid-switch project activate --session "$session" --pid $$) || return 1
+        while IFS= read -r line; do
+            [ -n "$line" ] && project_env+=("$line")
+        done <<< "$activation"
+        trap "command droid-switch project restore --session $session >/dev/null 2>&1" EXIT INT TERM HUP
+        command env "${project_env[@]}" droid "$@"
+        local rc=$?
+        trap - EXIT INT TERM HUP
+        command droid-switch project restore --session "$session" >/dev/null 2>&1
+        return $rc
+    fi
+    if command -v droid-switch >/dev/null 2>&1; then
+        api_key=$(command droid-switch current-key 2>/dev/null)
+    elif [ -f "$config_file" ]; then
+        api_key=$(grep -v '^[[:space:]]*//' "$config_file" 2>/dev/null | grep -o '"api_key"[[:space:]]*:[[:space:]]*"[^"]*"' | head -n 1 | sed 's/.*"api_key"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/')
     fi
     if [ -n "$api_key" ]; then
         FACTORY_API_KEY="$api_key" command droid "$@"
@@ -232,7 +232,7 @@ droid() {
 
     let marker_start = "# factory-ai-droid-switch Wrapper Start";
     let marker_end = "# factory-ai-droid-switch Wrapper End";
-    let current_version = "# Version: 2";
+    let current_version = "# Version: 6";
 
     let shell_configs = vec![
         home.join(".zshrc"),
@@ -254,7 +254,7 @@ droid() {
                         let new_content = remove_wrapper_block(&content, marker_start, marker_end);
                         let new_content = format!("{}\n{}\n", new_content.trim_end(), shell_function);
                         std::fs::write(&config_path, &new_content)
-                            .map_err(|e| format!("更新 {} 失败: {}", config_path.display(), e))?;
+                            .map_err(|e| AppError::io(&format!("更新 {} 失败", config_path.display()), e))?;
                         log::info!("已更新 shell 包装函数: {}", config_path.display());
                         continue;
                     }
@@ -262,7 +262,7 @@ droid() {
                     // 添加 shell 函数
                     let new_content = format!("{}\n{}\n", content.trim_end(), shell_function);
                     std::fs::write(&config_path, new_content)
-                        .map_err(|e| format!("写入 {} 失败: {}", config_path.display(), e))?;
+                        .map_err(|e| AppError::io(&format!("写入 {} 失败", config_path.display()), e))?;
 
                     log::info!("已安装 shell 包装函数到: {}", config_path.display());
                 }
@@ -278,7 +278,7 @@ droid() {
 
 /// Windows: 安装 PowerShell 函数和 CMD 批处理文件
 #[cfg(target_os = "windows")]
-fn install_windows_wrapper() -> Result<(), String> {
+fn install_windows_wrapper() -> Result<(), AppError> {
     // 安装 PowerShell 函数
     if let Err(e) = install_powershell_wrapper() {
         log::warn!("安装 PowerShell 包装函数失败: {}", e);
@@ -294,8 +294,8 @@ fn install_windows_wrapper() -> Result<(), String> {
 
 /// Windows: 安装 PowerShell 包装函数到 $PROFILE
 #[cfg(target_os = "windows")]
-fn install_powershell_wrapper() -> Result<(), String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+fn install_powershell_wrapper() -> Result<(), AppError> {
+    let home = dirs::home_dir().ok_or_else(|| AppError::other("无法获取用户主目录"))?;
     
     // 支持 Windows PowerShell 和 PowerShell Core
     let profile_paths = vec![
@@ -303,32 +303,84 @@ fn install_powershell_wrapper() -> Result<(), String> {
         home.join("Documents").join("PowerShell").join("Microsoft.PowerShell_profile.ps1"),
     ];
 
+    // 与 Unix 包装函数一致：找到 .droid-switch.toml 时通过 droid-switch 命令行开始项目会话，
+    // 项目环境变量只在本次运行期间设置（finally 中恢复，Ctrl-C 也会执行）；
+    // 无法解析项目密钥或未安装命令行时拒绝运行，不回退到全局密钥。
+    // 全局密钥优先通过命令行读取（支持 JSONC），否则用 ConvertFrom-Json 读取，解析失败时给出警告
     let powershell_function = r#"
 # factory-ai-droid-switch Wrapper Start
-# Version: 6
+# Version: 7
 function droid {
-    $configPath = "$env:USERPROFILE\.factory\config.json"
-    if (Test-Path $configPath) {
-        try {
-            $config = Get-Content $configPath -Raw | ConvertFrom-Json
-            if ($config.api_key) { $env:FACTORY_API_KEY = $config.api_key } else { $env:FACTORY_API_KEY = $null }
-        } catch { }
-    }
     # 查找真实的 droid 命令，排除 .factory 目录下的 wrapper，并支持 .exe/.cmd/.bat
     $droidCmd = Get-Command droid -All -ErrorAction SilentlyContinue | Where-Object { 
         $_.CommandType -eq 'Application' -and 
         ($_.Extension -ieq '.exe' -or $_.Extension -ieq '.cmd' -or $_.Extension -ieq '.bat') -and 
         $_.Source -notlike "*\.factory\*" 
     } | Select-Object -First 1
+    if (-not $droidCmd) {
+        Write-Error "droid command not found (checked .exe, .cmd, .bat). Please install Factory CLI first."
+        return
+    }
+    $switchCmd = Get-Command droid-switch -CommandType Application -ErrorAction SilentlyContinue | Select-Object -First 1
+
+    $projectFile = $null
+    $dir = (Get-Location).ProviderPath
+    while ($dir) {
+        $candidate = Join-Path $dir '.droid-switch.toml'
+        if (Test-Path -LiteralPath $candidate -PathType Leaf) { $projectFile = $candidate; break }
+        $parent = Split-Path -Parent $dir
+        if ($parent -eq $dir) { break }
+        $dir = $parent
+    }
+
+    if ($projectFile) {
+        if (-not $switchCmd) {
+            Write-Error "droid: found $projectFile but the droid-switch CLI is not installed; refusing to fall back to the global key"
+            return
+        }
+        $session = "$PID-$(Get-Random)"
+        $activation = & $switchCmd.Source project activate --session $session --pid $PID
+        if ($LASTEXITCODE -ne 0) {
+            Write-Error "droid: could not resolve the key for $projectFile; refusing to fall back to the global key"
+            return
+        }
+        $saved = @{}
+        foreach ($line in $activation) {
+            $name, $value = $line -split '=', 2
+            if (-not $value) { continue }
+            $saved[$name] = [Environment]::GetEnvironmentVariable($name, 'Process')
+            [Environment]::SetEnvironmentVariable($name, $value, 'Process')
+        }
+        try { & $droidCmd.Source @args }
+        finally {
+            foreach ($name in $saved.Keys) { [Environment]::SetEnvironmentVariable($name, $saved[$name], 'Process') }
+            & $switchCmd.Source project restore --session $session *> $null
+        }
+        return
+    }
 
-    if ($droidCmd) { & $droidCmd.Source @args }
-    else { Write-Error "droid command not found (checked .exe, .cmd, .bat). Please install Factory CLI first." }
+    $env:FACTORY_API_KEY = $null
+    if ($switchCmd) {
+        $key = & $switchCmd.Source current-key 2>$null
+        if ($key) { $env:FACTORY_API_KEY = $key }
+    } else {
+        $configPath = "$env:USERPROFILE\.factory\config.json"
+        if (Test-Path $configPath) {
+            try {
+                $config = Get-Content $configPath -Raw | ConvertFrom-Json
+                if ($config.api_key) { $env:FACTORY_API_KEY = $config.api_key }
+            } catch {
+                Write-Warning "droid: could not parse $configPath; install the droid-switch CLI to read configs with comments"
+            }
+        }
+    }
+    & $droidCmd.Source @args
 }
 # factory-ai-droid-switch Wrapper End"#;
 
     let marker_start = "# factory-ai-droid-switch Wrapper Start";
     let marker_end = "# factory-ai-droid-switch Wrapper End";
-    let current_version = "# Version: 6";
+    let current_version = "# Version: 7";
 
     for ps_profile in profile_paths {
         // 确保父目录存在（如果 Documents 存在）
@@ -385,39 +437,70 @@ function droid {
 
 /// Windows: 安装 CMD 批处理文件
 #[cfg(target_os = "windows")]
-fn install_cmd_wrapper() -> Result<(), String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+fn install_cmd_wrapper() -> Result<(), AppError> {
+    let home = dirs::home_dir().ok_or_else(|| AppError::other("无法获取用户主目录"))?;
     
     // 批处理文件路径
     let bin_dir = home.join(".factory").join("bin");
     let cmd_wrapper = bin_dir.join("droid.cmd");
     
-    // CMD wrapper: use PowerShell to find droid.exe/cmd/bat and read config
+    // CMD wrapper: use PowerShell to find droid.exe/cmd/bat and .droid-switch.toml.
+    // 项目会话与 PowerShell 包装函数一致；会话记录运行批处理的 cmd 进程 ID
     let batch_content = r#"@echo off
+rem factory-ai-droid-switch CMD wrapper Version: 2
 setlocal
-set "CF=%USERPROFILE%\.factory\config.json"
-set "FACTORY_API_KEY="
-if exist "%CF%" (
-    for /f "usebackq delims=" %%k in (`powershell -NoProfile -Command "try{$c=Get-Content '%CF%' -Raw|ConvertFrom-Json;if($c.api_key){Write-Output $c.api_key}}catch{}"`) do set "FACTORY_API_KEY=%%k"
-)
 set "DROID_EXE="
 for /f "delims=" %%e in ('powershell -NoProfile -Command "$E='.exe','.cmd','.bat';$P=$env:Path-split';';foreach($d in $P){if($d-like'*\.factory\*'){continue};foreach($x in $E){$f=Join-Path $d ('droid'+$x);if(Test-Path $f){$f;exit}}}"') do set "DROID_EXE=%%e"
-if defined DROID_EXE (
-    "%DROID_EXE%" %*
-    exit /b %errorlevel%
-)
+if not defined DROID_EXE goto notfound
+set "DS_PROJECT="
+for /f "delims=" %%p in ('powershell -NoProfile -Command "$d=(Get-Location).ProviderPath;while($d){$f=Join-Path $d '.droid-switch.toml';if(Test-Path -LiteralPath $f -PathType Leaf){$f;exit};$p=Split-Path -Parent $d;if($p -eq $d){break};$d=$p}"') do set "DS_PROJECT=%%p"
+if defined DS_PROJECT goto project
+set "FACTORY_API_KEY="
+where droid-switch >nul 2>nul
+if errorlevel 1 goto legacy
+for /f "delims=" %%k in ('droid-switch current-key 2^>nul') do set "FACTORY_API_KEY=%%k"
+goto run
+:legacy
+set "CF=%USERPROFILE%\.factory\config.json"
+if exist "%CF%" for /f "usebackq delims=" %%k in (`powershell -NoProfile -Command "try{$c=Get-Content '%CF%' -Raw|ConvertFrom-Json;if($c.api_key){Write-Output $c.api_key}}catch{[Console]::Error.WriteLine('droid: could not parse config.json; install the droid-switch CLI to read configs with comments')}"`) do set "FACTORY_API_KEY=%%k"
+:run
+call "%DROID_EXE%" %*
+exit /b %errorlevel%
+:project
+where droid-switch >nul 2>nul
+if errorlevel 1 goto nocli
+set "DS_PID="
+for /f %%i in ('powershell -NoProfile -Command "(Get-CimInstance Win32_Process -Filter ('ProcessId='+(Get-CimInstance Win32_Process -Filter ('ProcessId='+$PID)).ParentProcessId)).ParentProcessId"') do set "DS_PID=%%i"
+set "DS_SESSION=%DS_PID%-%RANDOM%%RANDOM%"
+set "DS_PID_ARG="
+if defined DS_PID set "DS_PID_ARG=--pid %DS_PID%"
+set "FACTORY_API_KEY="
+for /f "delims=" %%v in ('droid-switch project activate --session %DS_SESSION% %DS_PID_ARG%') do set "%%v"
+if not defined FACTORY_API_KEY goto noproject
+call "%DROID_EXE%" %*
+set "DS_RC=%errorlevel%"
+droid-switch project restore --session %DS_SESSION% >nul 2>nul
+exit /b %DS_RC%
+:nocli
+echo droid: found %DS_PROJECT% but the droid-switch CLI is not installed; refusing to fall back to the global key 1>&2
+exit /b 1
+:noproject
+echo droid: could not resolve the key for %DS_PROJECT%; refusing to fall back to the global key 1>&2
+exit /b 1
+:notfound
 echo droid command not found (checked .exe, .cmd, .bat)
 exit /b 1
 "#;
+    let current_version = "rem factory-ai-droid-switch CMD wrapper Version: 2";
 
     // 确保目录存在
     std::fs::create_dir_all(&bin_dir)
-        .map_err(|e| format!("创建 bin 目录失败: {}", e))?;
+        .map_err(|e| AppError::io("创建 bin 目录失败", e))?;
 
-    // 检查是否需要更新（检查新版本特征：usebackq）
+    // 检查是否需要更新（检查版本标记）
     if cmd_wrapper.exists() {
         let existing = std::fs::read_to_string(&cmd_wrapper).unwrap_or_default();
-        if existing.contains("usebackq") {
+        if existing.contains(current_version) {
             log::info!("CMD 批处理文件已是最新版本");
             return Ok(());
         }
@@ -427,7 +510,7 @@ exit /b 1
     // 写入批处理文件（确保使用 Windows CRLF 行尾符）
     let batch_content_crlf = batch_content.replace('\n', "\r\n");
     std::fs::write(&cmd_wrapper, batch_content_crlf)
-        .map_err(|e| format!("写入 CMD 批处理文件失败: {}", e))?;
+        .map_err(|e| AppError::io("写入 CMD 批处理文件失败", e))?;
 
     log::info!("已安装 CMD 批处理文件到: {}", cmd_wrapper.display());
     
@@ -441,7 +524,7 @@ exit /b 1
 
 /// Windows: 将目录添加到用户 PATH 环境变量（放在最前面确保优先级最高）
 #[cfg(target_os = "windows")]
-fn add_to_user_path(dir: &std::path::Path) -> Result<(), String> {
+fn add_to_user_path(dir: &std::path::Path) -> Result<(), AppError> {
     use std::process::Command;
     
     let dir_str = dir.to_string_lossy().to_string();
@@ -451,7 +534,7 @@ fn add_to_user_path(dir: &std::path::Path) -> Result<(), String> {
         .args(["-NoProfile", "-Command", 
             "[Environment]::GetEnvironmentVariable('Path', 'User')"])
         .output()
-        .map_err(|e| format!("执行 PowerShell 失败: {}", e))?;
+        .map_err(|e| AppError::other(format!("执行 PowerShell 失败: {}", e)))?;
     
     let current_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
     
@@ -489,11 +572,11 @@ fn add_to_user_path(dir: &std::path::Path) -> Result<(), String> {
     let output = Command::new("powershell")
         .args(["-NoProfile", "-Command", &set_cmd])
         .output()
-        .map_err(|e| format!("设置 PATH 失败: {}", e))?;
+        .map_err(|e| AppError::other(format!("设置 PATH 失败: {}", e)))?;
     
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("设置 PATH 失败: {}", stderr));
+        return Err(AppError::other(format!("设置 PATH 失败: {}", stderr)));
     }
     
     log::info!("已将 {} 添加到用户 PATH（需要重启 CMD 生效）", dir_str);
@@ -517,126 +600,11 @@ fn broadcast_environment_change() {
 }
 
 /// 获取当前配置文件中的 Factory API Key
-pub fn get_factory_api_key_env() -> Result<Option<String>, String> {
-    let config_path = get_factory_config_path()?;
-    
-    if !config_path.exists() {
+pub fn get_factory_api_key_env() -> Result<Option<String>, AppError> {
+    let Some(content) = read_factory_config()? else {
         return Ok(None);
-    }
-
-    let content = std::fs::read_to_string(&config_path)
-        .map_err(|e| format!("读取 config.json 失败: {}", e))?;
-    let config: Value = serde_json::from_str(&content)
-        .unwrap_or_else(|_| serde_json::json!({}));
+    };
+    let config = jsonc::parse(&content).map_err(|e| AppError::parse("解析 config.json 失败", e))?;
 
     Ok(config.get("api_key").and_then(|v| v.as_str()).map(|s| s.to_string()))
 }
-
-/// 查询单个 API Key 的余额
-pub async fn check_balance(api_key: &str) -> Result<BalanceInfo, String> {
-    log::info!("开始查询余额，API Key: {}...", &api_key[..api_key.len().min(10)]);
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(15))
-        .build()
-        .map_err(|e| {
-            log::error!("创建 HTTP 客户端失败: {}", e);
-            format!("创建 HTTP 客户端失败: {}", e)
-        })?;
-
-    log::info!("发送请求到 Factory.ai API...");
-    let response = client
-        .get("https://app.factory.ai/api/organization/members/chat-usage")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("x-factory-client", "web-browser")
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("请求失败: {}", e);
-            format!("请求失败: {}", e)
-        })?;
-
-    let status = response.status();
-    log::info!("收到响应，状态码: {}", status);
-
-    if status.as_u16() != 200 {
-        let text = response.text().await.unwrap_or_default();
-        log::error!("HTTP 错误 {}: {}", status, text);
-        return Err(format!("HTTP {}: {}", status, text));
-    }
-
-    // 先获取响应文本用于调试
-    let response_text = response.text().await
-        .map_err(|e| {
-            log::error!("读取响应文本失败: {}", e);
-            format!("读取响应失败: {}", e)
-        })?;
-
-    log::info!("响应内容: {}", response_text);
-
-    // 解析 JSON
-    let api_response: FactoryApiResponse = serde_json::from_str(&response_text)
-        .map_err(|e| {
-            log::error!("JSON 解析失败: {}，响应内容: {}", e, response_text);
-            format!("解析响应失败: {}，原始响应: {}", e, response_text)
-        })?;
-
-    let usage = api_response.usage.standard;
-    let remaining = usage.total_allowance.saturating_sub(usage.user_tokens);
-    let percent_used = usage.used_ratio * 100.0;
-    let exceeded = usage.used_ratio > 1.0;
-
-    // 提取到期时间：endDate字段（Unix时间戳，毫秒）
-    let expiry_date = api_response.usage.end_date.map(|timestamp| {
-        // 将毫秒时间戳转换为ISO格式字符串
-        use chrono::{Utc, TimeZone};
-        let datetime = Utc.timestamp_millis_opt(timestamp).unwrap();
-        datetime.to_rfc3339()
-    });
-
-    log::info!("余额查询成功: 已用 {}, 总配额 {}, 剩余 {}",
-        usage.user_tokens, usage.total_allowance, remaining);
-
-    if let Some(ref expiry) = expiry_date {
-        log::info!("到期时间: {}", expiry);
-    }
-
-    Ok(BalanceInfo {
-        used: usage.user_tokens,
-        allowance: usage.total_allowance,
-        remaining,
-        overage: usage.org_overage_used,
-        used_ratio: usage.used_ratio,
-        percent_used,
-        exceeded,
-        expiry_date,
-    })
-}
-
-/// 批量查询 API Keys 的余额
-pub async fn batch_check_balances(
-    api_keys: Vec<String>,
-) -> Result<std::collections::HashMap<String, BalanceInfo>, String> {
-    use std::collections::HashMap;
-
-    let mut results = HashMap::new();
-
-    for (i, key) in api_keys.iter().enumerate() {
-        match check_balance(key).await {
-            Ok(balance) => {
-                results.insert(key.clone(), balance);
-            }
-            Err(e) => {
-                log::warn!("查询密钥 {} 余额失败: {}", key, e);
-            }
-        }
-
-        // 延迟 200ms 避免请求过快
-        if i < api_keys.len() - 1 {
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        }
-    }
-
-    Ok(results)
-}
@@ -0,0 +1,176 @@
+use crate::factory_client::FactoryApiError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// 应用错误
+///
+/// 序列化为 `{ code, message, context }`，前端和脚本可按 `code` 区分错误类型，
+/// 不必匹配错误信息文本。
+#[derive(Debug, Clone)]
+pub enum AppError {
+    /// 找不到密钥、模型等
+    NotFound { entity: &'static str, id: String },
+    /// 名称、API Key 等重复
+    Duplicate {
+        field: &'static str,
+        message: String,
+    },
+    /// API Key 格式无效
+    InvalidKey(String),
+    /// 参数或设置无效
+    InvalidInput(String),
+    /// API Key 被拒绝（401/403，如已吊销）
+    Unauthorized { status: u16, message: String },
+    /// 连接失败等网络错误
+    Network(String),
+    /// 请求超时
+    Timeout(String),
+    /// 其他 HTTP 错误（429、5xx 等）
+    Http { status: u16, message: String },
+    /// 文件读写失败
+    Io(String),
+    /// JSON、TOML 等解析失败
+    Parse(String),
+    /// 密钥库未解锁或口令错误
+    Locked(String),
+    /// 系统环境变量、钥匙串等其他错误
+    Other(String),
+}
+
+impl AppError {
+    pub fn not_found(entity: &'static str, id: impl Into<String>) -> Self {
+        Self::NotFound {
+            entity,
+            id: id.into(),
+        }
+    }
+
+    pub fn duplicate(field: &'static str, message: impl Into<String>) -> Self {
+        Self::Duplicate {
+            field,
+            message: message.into(),
+        }
+    }
+
+    /// 文件读写错误，context 描述失败的操作
+    pub fn io(context: &str, e: impl fmt::Display) -> Self {
+        Self::Io(format!("{}: {}", context, e))
+    }
+
+    /// 解析错误，context 描述解析的内容
+    pub fn parse(context: &str, e: impl fmt::Display) -> Self {
+        Self::Parse(format!("{}: {}", context, e))
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::InvalidInput(message.into())
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::Other(message.into())
+    }
+
+    /// 稳定的错误代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "NotFound",
+            Self::Duplicate { .. } => "Duplicate",
+            Self::InvalidKey(_) => "InvalidKey",
+            Self::InvalidInput(_) => "InvalidInput",
+            Self::Unauthorized { .. } => "Unauthorized",
+            Self::Network(_) => "Network",
+            Self::Timeout(_) => "Timeout",
+            Self::Http { .. } => "Http",
+            Self::Io(_) => "Io",
+            Self::Parse(_) => "Parse",
+            Self::Locked(_) => "Locked",
+            Self::Other(_) => "Other",
+        }
+    }
+
+    /// 结构化的错误上下文
+    fn context(&self) -> Option<Value> {
+        match self {
+            Self::NotFound { entity, id } => Some(json!({ "entity": entity, "id": id })),
+            Self::Duplicate { field, .. } => Some(json!({ "field": field })),
+            Self::Unauthorized { status, .. } | Self::Http { status, .. } => {
+                Some(json!({ "status": status }))
+            }
+            _ => None,
+        }
+    }
+
+    /// 命令行退出码（按错误类型区分，便于脚本判断）
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::InvalidInput(_) | Self::InvalidKey(_) => 2,
+            Self::NotFound { .. } => 3,
+            Self::Duplicate { .. } => 4,
+            Self::Locked(_) => 5,
+            Self::Unauthorized { .. } => 6,
+            Self::Network(_) | Self::Timeout(_) | Self::Http { .. } => 7,
+            Self::Io(_) | Self::Parse(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { entity, id } => write!(f, "未找到{}: {}", entity, id),
+            Self::Unauthorized { status, message } | Self::Http { status, message } => {
+                write!(f, "HTTP {}: {}", status, message)
+            }
+            Self::Duplicate { message, .. }
+            | Self::InvalidKey(message)
+            | Self::InvalidInput(message)
+            | Self::Network(message)
+            | Self::Timeout(message)
+            | Self::Io(message)
+            | Self::Parse(message)
+            | Self::Locked(message)
+            | Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let context = self.context();
+        let mut state =
+            serializer.serialize_struct("AppError", if context.is_some() { 3 } else { 2 })?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(context) = context {
+            state.serialize_field("context", &context)?;
+        } else {
+            state.skip_field("context")?;
+        }
+        state.end()
+    }
+}
+
+impl From<FactoryApiError> for AppError {
+    fn from(e: FactoryApiError) -> Self {
+        match e {
+            FactoryApiError::Network(message) => Self::Network(format!("请求失败: {}", message)),
+            FactoryApiError::Timeout(message) => Self::Timeout(format!("请求超时: {}", message)),
+            FactoryApiError::Http { status, body, .. } if status == 401 || status == 403 => {
+                Self::Unauthorized {
+                    status,
+                    message: body,
+                }
+            }
+            FactoryApiError::Http { status, body, .. } => Self::Http {
+                status,
+                message: body,
+            },
+            FactoryApiError::Parse(message) => Self::Parse(message),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::AppError;
+use crate::models::{BalanceInfo, ExpiryAlert, ExpirySettings, Provider, ProviderStatus};
+use crate::provider_manager::{load_config, update_config};
+use chrono::{DateTime, Duration, Local, Utc};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// 获取到期提醒设置
+pub fn get_settings() -> Result<ExpirySettings, AppError> {
+    Ok(load_config()?.expiry)
+}
+
+/// 保存到期提醒设置
+pub fn set_settings(mut settings: ExpirySettings) -> Result<(), AppError> {
+    if settings.warn_days.contains(&0) {
+        return Err(AppError::invalid_input("到期提醒天数必须大于 0"));
+    }
+    settings.warn_days.sort_unstable_by(|a, b| b.cmp(a));
+    settings.warn_days.dedup();
+
+    update_config(|config| {
+        config.expiry = settings;
+        Ok(())
+    })?;
+    log::info!("已更新到期提醒设置");
+    Ok(())
+}
+
+/// 余额信息中的到期时间
+pub fn balance_expiry_date(balance: &BalanceInfo) -> Option<DateTime<Utc>> {
+    balance
+        .expiry_date
+        .as_deref()
+        .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// 密钥最近一次查询到的到期时间
+pub fn expiry_date(provider: &Provider) -> Option<DateTime<Utc>> {
+    balance_expiry_date(provider.balance.as_ref()?)
+}
+
+/// 距到期的整天数（不足一天为 0，已过期为负数），没有到期时间时为空
+pub fn days_until_expiry(provider: &Provider, now: DateTime<Utc>) -> Option<i64> {
+    expiry_date(provider).map(|expiry| (expiry - now).num_seconds().div_euclid(SECONDS_PER_DAY))
+}
+
+/// 到期提示（如“3 天后到期”），用于托盘和命令行
+pub fn expiry_hint(provider: &Provider, now: DateTime<Utc>) -> Option<String> {
+    Some(match days_until_expiry(provider, now)? {
+        days if days < 0 => "已过期".to_string(),
+        0 => "1 天内到期".to_string(),
+        days => format!("{} 天后到期", days),
+    })
+}
+
+/// 根据到期时间标记密钥是否过期，返回需要发出的提醒并更新提醒状态
+fn evaluate(
+    provider: &mut Provider,
+    settings: &ExpirySettings,
+    now: DateTime<Utc>,
+) -> Option<ExpiryAlert> {
+    provider.balance.as_ref()?;
+    let Some(expiry) = expiry_date(provider) else {
+        provider.expired = false;
+        provider.alert_state.expiry_warned_days.clear();
+        return None;
+    };
+
+    let remaining = expiry - now;
+    let days = days_until_expiry(provider, now)?;
+    let was_expired = provider.expired;
+    provider.expired = remaining <= Duration::zero();
+    if provider.expired {
+        provider.status = ProviderStatus::Expired;
+    }
+
+    // 到期时间延后（如续期）后允许再次提醒
+    let state = &mut provider.alert_state;
+    state
+        .expiry_warned_days
+        .retain(|d| remaining <= Duration::days(i64::from(*d)));
+    if !settings.enabled {
+        return None;
+    }
+
+    let date = expiry.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    let message = if provider.expired {
+        if was_expired {
+            return None;
+        }
+        format!("密钥 {} 已于 {} 过期", provider.name, date)
+    } else {
+        let due: Vec<u32> = settings
+            .warn_days
+            .iter()
+            .copied()
+            .filter(|d| {
+                remaining <= Duration::days(i64::from(*d)) && !state.expiry_warned_days.contains(d)
+            })
+            .collect();
+        state.expiry_warned_days.extend(&due);
+        // 一次跨过多个提醒点时只提醒一次
+        if due.is_empty() {
+            return None;
+        }
+        match days {
+            0 => format!("密钥 {} 将在 1 天内（{}）到期", provider.name, date),
+            days => format!("密钥 {} 将在 {} 天后（{}）到期", provider.name, days, date),
+        }
+    };
+
+    Some(ExpiryAlert {
+        provider_id: provider.id.clone(),
+        provider_name: provider.name.clone(),
+        expiry_date: expiry.to_rfc3339(),
+        days_until_expiry: days,
+        expired: provider.expired,
+        message,
+    })
+}
+
+/// 检查所有密钥的到期时间：标记已过期的密钥，返回新的到期提醒
+///
+/// 提醒状态保存在配置中，重启后不会重复提醒。过期的当前密钥由自动轮换切换走。
+pub fn check_expiry() -> Result<Vec<ExpiryAlert>, AppError> {
+    let now = Utc::now();
+    let alerts = update_config(|config| {
+        let settings = config.expiry.clone();
+        Ok(config
+            .providers
+            .iter_mut()
+            .filter_map(|p| evaluate(p, &settings, now))
+            .collect::<Vec<_>>())
+    })?;
+
+    for alert in &alerts {
+        log::info!("到期提醒: {}", alert.message);
+    }
+    Ok(alerts)
+}
@@ -0,0 +1,275 @@
+use crate::error::AppError;
+use crate::models::{AppConfig, BalanceInfo, NetworkSettings, TierUsage};
+use crate::network;
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Factory.ai API 默认地址
+pub const DEFAULT_BASE_URL: &str = "https://app.factory.ai";
+
+/// 覆盖 API 地址的环境变量（优先级高于配置文件，便于连接测试环境）
+pub const BASE_URL_ENV: &str = "FACTORY_API_BASE_URL";
+
+/// 用量查询接口路径
+const CHAT_USAGE_PATH: &str = "/api/organization/members/chat-usage";
+
+/// Factory.ai API 响应结构（忽略未知字段）
+#[derive(Debug, Deserialize)]
+struct FactoryApiResponse {
+    usage: Usage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Usage {
+    standard: TierUsageResponse,
+    /// 计费周期开始时间（Unix 时间戳，毫秒）
+    start_date: Option<i64>,
+    /// 到期时间（Unix 时间戳，毫秒）
+    end_date: Option<i64>,
+    /// premium 等其他档位，以及其他未知字段
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TierUsageResponse {
+    user_tokens: u64,
+    total_allowance: u64,
+    #[serde(default)]
+    org_overage_used: u64,
+    used_ratio: f64,
+    #[serde(default)]
+    org_total_tokens_used: Option<u64>,
+    #[serde(default)]
+    org_overage_limit: Option<u64>,
+}
+
+impl TierUsageResponse {
+    fn to_tier(&self, name: &str) -> TierUsage {
+        TierUsage {
+            name: name.to_string(),
+            used: self.user_tokens,
+            allowance: self.total_allowance,
+            remaining: self.total_allowance.saturating_sub(self.user_tokens),
+            overage: self.org_overage_used,
+            used_ratio: self.used_ratio,
+            org_used: self.org_total_tokens_used,
+            overage_limit: self.org_overage_limit,
+        }
+    }
+}
+
+/// Factory.ai API 请求错误
+#[derive(Debug, Clone)]
+pub enum FactoryApiError {
+    /// 连接失败等网络错误
+    Network(String),
+    /// 请求超时
+    Timeout(String),
+    /// 非 200 响应
+    Http {
+        status: u16,
+        body: String,
+        /// 429 响应中的 Retry-After
+        retry_after: Option<Duration>,
+    },
+    /// 响应无法解析
+    Parse(String),
+}
+
+impl FactoryApiError {
+    /// 网络错误、5xx 和 429 可以重试
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) | Self::Timeout(_) => true,
+            Self::Http { status, .. } => *status == 429 || *status >= 500,
+            Self::Parse(_) => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Http { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FactoryApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "请求失败: {}", e),
+            Self::Timeout(e) => write!(f, "请求超时: {}", e),
+            Self::Http { status, body, .. } => write!(f, "HTTP {}: {}", status, body),
+            Self::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Factory.ai API 客户端
+#[async_trait]
+pub trait FactoryClient: Send + Sync {
+    /// 查询 API Key 的用量（余额）
+    async fn chat_usage(&self, api_key: &str) -> Result<BalanceInfo, FactoryApiError>;
+}
+
+/// 基于 reqwest 的 HTTP 客户端
+pub struct HttpFactoryClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl HttpFactoryClient {
+    /// 使用默认网络设置创建客户端
+    pub fn new(base_url: &str) -> Result<Self, AppError> {
+        Self::with_settings(base_url, &NetworkSettings::default())
+    }
+
+    /// 使用指定网络设置创建客户端（复用共享的 HTTP 客户端）
+    pub fn with_settings(base_url: &str, settings: &NetworkSettings) -> Result<Self, AppError> {
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: network::shared_client(settings)?,
+        })
+    }
+
+    /// 按配置创建客户端（环境变量 > 配置文件 > 默认地址）
+    pub fn from_config(config: &AppConfig) -> Result<Self, AppError> {
+        Self::with_settings(&resolve_base_url(config), &config.network)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+#[async_trait]
+impl FactoryClient for HttpFactoryClient {
+    async fn chat_usage(&self, api_key: &str) -> Result<BalanceInfo, FactoryApiError> {
+        let url = format!("{}{}", self.base_url, CHAT_USAGE_PATH);
+        log::info!("发送请求到 {}...", url);
+        let response = self
+            .http
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("x-factory-client", "web-browser")
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .map_err(|e| {
+                log::error!("请求失败: {}", e);
+                if e.is_timeout() {
+                    FactoryApiError::Timeout(e.to_string())
+                } else {
+                    FactoryApiError::Network(e.to_string())
+                }
+            })?;
+
+        let status = response.status();
+        log::info!("收到响应，状态码: {}", status);
+
+        if status.as_u16() != 200 {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after);
+            let text = response.text().await.unwrap_or_default();
+            log::error!("HTTP 错误 {}: {}", status, text);
+            return Err(FactoryApiError::Http {
+                status: status.as_u16(),
+                body: text,
+                retry_after,
+            });
+        }
+
+        let response_text = response.text().await.map_err(|e| {
+            log::error!("读取响应文本失败: {}", e);
+            FactoryApiError::Network(format!("读取响应失败: {}", e))
+        })?;
+        log::debug!("响应内容: {}", response_text);
+
+        parse_chat_usage(&response_text)
+    }
+}
+
+/// 解析 Retry-After（秒数或 HTTP 日期）
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// 解析 API 地址：环境变量 FACTORY_API_BASE_URL > 配置中的 apiBaseUrl > 默认地址
+pub fn resolve_base_url(config: &AppConfig) -> String {
+    std::env::var(BASE_URL_ENV)
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .or_else(|| {
+            config
+                .api_base_url
+                .clone()
+                .filter(|url| !url.trim().is_empty())
+        })
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+}
+
+/// 解析用量接口的响应
+///
+/// 汇总字段来自 standard 档位；其他可识别的档位放入 `tiers`，原始响应保存在 `raw` 中。
+pub fn parse_chat_usage(body: &str) -> Result<BalanceInfo, FactoryApiError> {
+    let parse_error = |e: serde_json::Error| {
+        log::error!("JSON 解析失败: {}，响应内容: {}", e, body);
+        FactoryApiError::Parse(format!("解析响应失败: {}，原始响应: {}", e, body))
+    };
+    let raw: serde_json::Value = serde_json::from_str(body).map_err(parse_error)?;
+    let api_response: FactoryApiResponse =
+        serde_json::from_value(raw.clone()).map_err(parse_error)?;
+
+    let usage = api_response.usage;
+    let standard = &usage.standard;
+    let remaining = standard
+        .total_allowance
+        .saturating_sub(standard.user_tokens);
+    let percent_used = standard.used_ratio * 100.0;
+    let exceeded = standard.used_ratio > 1.0;
+
+    // 时间字段均为 Unix 时间戳（毫秒）
+    let timestamp = |millis: Option<i64>| {
+        millis
+            .and_then(|timestamp| Utc.timestamp_millis_opt(timestamp).single())
+            .map(|datetime| datetime.to_rfc3339())
+    };
+
+    // 其他档位结构与 standard 相同，无法识别的字段忽略
+    let tiers = std::iter::once(standard.to_tier("standard"))
+        .chain(usage.other.iter().filter_map(|(name, value)| {
+            serde_json::from_value::<TierUsageResponse>(value.clone())
+                .ok()
+                .map(|tier| tier.to_tier(name))
+        }))
+        .collect();
+
+    Ok(BalanceInfo {
+        used: standard.user_tokens,
+        allowance: standard.total_allowance,
+        remaining,
+        overage: standard.org_overage_used,
+        used_ratio: standard.used_ratio,
+        percent_used,
+        exceeded,
+        expiry_date: timestamp(usage.end_date),
+        period_start: timestamp(usage.start_date),
+        tiers,
+        raw: Some(raw),
+    })
+}
@@ -0,0 +1,1195 @@
+use crate::audit::{self, AuditTarget};
+use crate::error::AppError;
+use crate::models::{
+    AppConfig, AuditAction, AuditFilter, AuditRecord, AuditSource, BalanceCheckResult,
+    BalanceCheckSettings, BalanceForecast, BalanceInfo, BalanceRefreshSettings, BalanceSnapshot,
+    BalanceUpdatedEvent, ConnectivityReport, ExpirySettings, HistoryRange, ImportReport,
+    KeyValidation, ModelCatalogSettings, ModelCatalogStatus, ModelInfo, ModelQuota,
+    NetworkSettings, Provider, QuotaAlertSettings, ReasoningLevel, RefreshAllResult,
+    RotationPolicy, SecretBackend, TransferFormat, VaultStatus,
+};
+use crate::project::{self, ProjectBinding};
+use crate::{
+    balance, balance_history, droid_config, expiry, factory_client, health, import_export,
+    model_catalog, model_manager, network, provider_manager, quota_alert, rotation, secret_store,
+    vault,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::{
+    menu::{CheckMenuItem, Menu, MenuBuilder, MenuItem},
+    tray::{TrayIconBuilder, TrayIconEvent},
+    Emitter, Manager,
+};
+use tauri_plugin_notification::NotificationExt;
+
+/// 在阻塞线程池中执行同步操作
+///
+/// 配置锁、配置文件读写和系统钥匙串访问都会阻塞，直接在异步命令中执行会占用 tokio 工作线程，
+/// 其他进程持有配置锁时会拖住整个运行时。
+async fn run_blocking<T, F>(f: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::other(format!("后台任务执行失败: {}", e)))?
+}
+
+// ==================== 托盘菜单相关 ====================
+
+/// 创建动态托盘菜单
+fn create_tray_menu(app: &tauri::AppHandle) -> Result<Menu<tauri::Wry>, AppError> {
+    let config = provider_manager::load_config()?;
+    let mut menu_builder = MenuBuilder::new(app);
+
+    // 顶部：打开主界面
+    let show_main_item = MenuItem::with_id(app, "show_main", "打开主界面", true, None::<&str>)
+        .map_err(|e| AppError::other(format!("创建打开主界面菜单失败: {}", e)))?;
+    menu_builder = menu_builder.item(&show_main_item).separator();
+
+    // 密钥列表
+    if !config.providers.is_empty() {
+        let now = chrono::Utc::now();
+        for provider in &config.providers {
+            let is_current = config.active_provider_id.as_ref() == Some(&provider.id);
+            // 显示最近一次查询到的剩余额度（及当前模型下折算的 tokens）、查询时间（已过时）、
+            // 刷新失败和到期提示
+            let hints: Vec<String> = provider
+                .balance
+                .as_ref()
+                .map(|info| format!("剩余 {}", balance::format_tokens(info.remaining)))
+                .into_iter()
+                .chain(
+                    provider
+                        .balance
+                        .as_ref()
+                        .and_then(|info| model_manager::effective_remaining_hint(&config, info)),
+                )
+                .chain(
+                    balance::format_age(provider, now)
+                        .filter(|_| provider.balance_stale)
+                        .map(|age| format!("{}查询", age)),
+                )
+                .chain(provider.last_error.as_ref().map(|_| "刷新失败".to_string()))
+                .chain(
+                    health::status_hint(provider.status)
+                        .filter(|_| !provider.expired)
+                        .map(str::to_string),
+                )
+                .chain(expiry::expiry_hint(provider, now))
+                .collect();
+            let label = if hints.is_empty() {
+                provider.name.clone()
+            } else {
+                format!("{}（{}）", provider.name, hints.join("，"))
+            };
+            let item = CheckMenuItem::with_id(
+                app,
+                format!("provider_{}", provider.id),
+                &label,
+                true,
+                is_current,
+                None::<&str>,
+            )
+            .map_err(|e| AppError::other(format!("创建菜单项失败: {}", e)))?;
+            menu_builder = menu_builder.item(&item);
+        }
+
+        // 如果有当前密钥，添加停用按钮
+        if config.active_provider_id.is_some() {
+            menu_builder = menu_builder.separator();
+            let disable_item =
+                MenuItem::with_id(app, "disable_provider", "停用当前密钥", true, None::<&str>)
+                    .map_err(|e| AppError::other(format!("创建停用菜单失败: {}", e)))?;
+            menu_builder = menu_builder.item(&disable_item);
+        }
+    } else {
+        let empty_hint = MenuItem::with_id(app, "empty", "(无密钥)", false, None::<&str>)
+            .map_err(|e| AppError::other(format!("创建空提示失败: {}", e)))?;
+        menu_builder = menu_builder.item(&empty_hint);
+    }
+
+    // 分隔符和退出菜单
+    let quit_item = MenuItem::with_id(app, "quit", "退出", true, None::<&str>)
+        .map_err(|e| AppError::other(format!("创建退出菜单失败: {}", e)))?;
+
+    menu_builder = menu_builder.separator().item(&quit_item);
+
+    menu_builder
+        .build()
+        .map_err(|e| AppError::other(format!("构建菜单失败: {}", e)))
+}
+
+/// 处理托盘菜单事件
+fn handle_tray_menu_event(app: &tauri::AppHandle, event_id: &str) {
+    log::info!("处理托盘菜单事件: {}", event_id);
+
+    match event_id {
+        "show_main" => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        "quit" => {
+            log::info!("退出应用");
+            app.exit(0);
+        }
+        "disable_provider" => {
+            log::info!("停用密钥");
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = disable_provider_internal(&app_handle, AuditSource::Tray).await {
+                    log::error!("停用密钥失败: {}", e);
+                }
+            });
+        }
+        id if id.starts_with("provider_") => {
+            let provider_id = id.strip_prefix("provider_").unwrap();
+            log::info!("切换到密钥: {}", provider_id);
+
+            let app_handle = app.clone();
+            let provider_id = provider_id.to_string();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) =
+                    switch_provider_internal(&app_handle, provider_id, AuditSource::Tray).await
+                {
+                    log::error!("切换密钥失败: {}", e);
+                }
+            });
+        }
+        _ => {
+            log::warn!("未处理的菜单事件: {}", event_id);
+        }
+    }
+}
+
+/// 内部切换密钥函数
+async fn switch_provider_internal(
+    app: &tauri::AppHandle,
+    provider_id: String,
+    source: AuditSource,
+) -> Result<(), AppError> {
+    // 执行切换
+    let id = provider_id.clone();
+    let provider = run_blocking(move || {
+        let result = provider_manager::switch_provider(id.clone());
+        audit::record(
+            AuditAction::SwitchProvider,
+            source,
+            AuditTarget::provider(&id),
+            None,
+            &result,
+        );
+        result
+    })
+    .await?;
+    log::info!("已切换到密钥: {}", provider.name);
+
+    // 切换成功后重新创建托盘菜单
+    rebuild_tray_menu(app).await;
+
+    // 发射事件到前端，通知密钥已切换
+    let event_data = serde_json::json!({
+        "providerId": provider_id
+    });
+    if let Err(e) = app.emit("provider-switched", event_data) {
+        log::error!("发射密钥切换事件失败: {}", e);
+    }
+
+    Ok(())
+}
+
+/// 重新创建托盘菜单（读取配置，在阻塞线程池中执行）
+async fn rebuild_tray_menu(app: &tauri::AppHandle) {
+    let app = app.clone();
+    let result = run_blocking(move || {
+        let new_menu = create_tray_menu(&app)?;
+        if let Some(tray) = app.tray_by_id("main") {
+            if let Err(e) = tray.set_menu(Some(new_menu)) {
+                log::error!("更新托盘菜单失败: {}", e);
+            }
+        }
+        Ok(())
+    })
+    .await;
+    if let Err(e) = result {
+        log::error!("创建托盘菜单失败: {}", e);
+    }
+}
+
+/// 余额刷新后检查额度提醒，发送桌面通知和 quota-alert 事件
+async fn notify_quota_alerts(app: &tauri::AppHandle) {
+    let alerts = match run_blocking(quota_alert::check_alerts).await {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            log::error!("检查额度提醒失败: {}", e);
+            return;
+        }
+    };
+
+    for alert in alerts {
+        if let Err(e) = app
+            .notification()
+            .builder()
+            .title("Droid Switch 额度提醒")
+            .body(&alert.message)
+            .show()
+        {
+            log::error!("发送桌面通知失败: {}", e);
+        }
+        if let Err(e) = app.emit("quota-alert", alert) {
+            log::error!("发射额度提醒事件失败: {}", e);
+        }
+    }
+}
+
+/// 检查到期时间（标记过期密钥），发送桌面通知和 expiry-alert 事件
+async fn notify_expiry_alerts(app: &tauri::AppHandle) {
+    let alerts = match run_blocking(expiry::check_expiry).await {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            log::error!("检查到期时间失败: {}", e);
+            return;
+        }
+    };
+
+    for alert in alerts {
+        if let Err(e) = app
+            .notification()
+            .builder()
+            .title("Droid Switch 到期提醒")
+            .body(&alert.message)
+            .show()
+        {
+            log::error!("发送桌面通知失败: {}", e);
+        }
+        if let Err(e) = app.emit("expiry-alert", alert) {
+            log::error!("发射到期提醒事件失败: {}", e);
+        }
+    }
+}
+
+/// 余额刷新后按轮换策略自动切换密钥
+async fn apply_auto_rotation(app: &tauri::AppHandle) {
+    let result = run_blocking(|| {
+        let result = rotation::rotate_if_needed();
+        match &result {
+            Ok(Some(outcome)) => audit::record(
+                AuditAction::SwitchProvider,
+                AuditSource::Auto,
+                AuditTarget::named(
+                    Some(outcome.provider.id.clone()),
+                    outcome.provider.name.clone(),
+                ),
+                Some(format!(
+                    "自动轮换（原密钥 {}）: {}",
+                    outcome.from_id, outcome.reason
+                )),
+                &result,
+            ),
+            Ok(None) => {}
+            Err(_) => audit::record(
+                AuditAction::SwitchProvider,
+                AuditSource::Auto,
+                AuditTarget::active_provider(),
+                Some("自动轮换".to_string()),
+                &result,
+            ),
+        }
+        result
+    })
+    .await;
+
+    match result {
+        Ok(Some(outcome)) => {
+            rebuild_tray_menu(app).await;
+
+            let event_data = serde_json::json!({
+                "providerId": outcome.provider.id,
+                "previousProviderId": outcome.from_id,
+                "reason": outcome.reason,
+            });
+            if let Err(e) = app.emit("provider-switched", event_data) {
+                log::error!("发射密钥切换事件失败: {}", e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("自动轮换密钥失败: {}", e),
+    }
+}
+
+/// 生成余额刷新进度回调：每个密钥查询完成时发射 balance-updated 事件
+fn balance_progress_emitter(
+    app: &tauri::AppHandle,
+) -> impl FnMut(&Provider, &Result<BalanceInfo, AppError>, usize) + '_ {
+    let mut completed = 0;
+    move |provider: &Provider, result: &Result<BalanceInfo, AppError>, total: usize| {
+        completed += 1;
+        let event_data = BalanceUpdatedEvent {
+            provider_id: provider.id.clone(),
+            provider_name: provider.name.clone(),
+            balance: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().cloned(),
+            completed,
+            total,
+        };
+        if let Err(e) = app.emit("balance-updated", event_data) {
+            log::error!("发射余额更新事件失败: {}", e);
+        }
+    }
+}
+
+/// 定时检查到期时间的间隔
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 定时检查模型清单是否需要重新获取的间隔
+const CATALOG_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 后台定时刷新余额：每分钟检查一次设置，到达刷新间隔时刷新过期的余额
+///
+/// 到期时间与余额查询无关，每小时单独检查一次，并更新托盘中的到期提示。
+async fn run_balance_scheduler(app: tauri::AppHandle) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(60));
+    let mut last_run: Option<Instant> = None;
+    let mut last_expiry_check: Option<Instant> = None;
+    let mut last_catalog_check: Option<Instant> = None;
+
+    loop {
+        ticker.tick().await;
+
+        if last_catalog_check.is_none_or(|t| t.elapsed() >= CATALOG_CHECK_INTERVAL) {
+            last_catalog_check = Some(Instant::now());
+            refresh_model_catalog_if_needed(&app).await;
+        }
+
+        if last_expiry_check.is_none_or(|t| t.elapsed() >= EXPIRY_CHECK_INTERVAL) {
+            last_expiry_check = Some(Instant::now());
+            notify_expiry_alerts(&app).await;
+            apply_auto_rotation(&app).await;
+            rebuild_tray_menu(&app).await;
+        }
+
+        let settings = match run_blocking(balance::get_refresh_settings).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("读取定时刷新设置失败: {}", e);
+                continue;
+            }
+        };
+        if !settings.enabled {
+            continue;
+        }
+        let interval = Duration::from_secs(settings.interval_minutes.max(1) * 60);
+        if last_run.is_some_and(|t| t.elapsed() < interval) {
+            continue;
+        }
+
+        // 密钥库锁定时无法读取 API Key，下次检查时再试
+        if run_blocking(provider_manager::ensure_vault_unlocked)
+            .await
+            .is_err()
+        {
+            log::debug!("密钥库已锁定，跳过定时刷新");
+            continue;
+        }
+
+        last_run = Some(Instant::now());
+        refresh_stale_balances(&app, &settings).await;
+    }
+}
+
+/// 模型清单超过刷新间隔时重新获取，成功后通知前端重新加载模型列表
+async fn refresh_model_catalog_if_needed(app: &tauri::AppHandle) {
+    match run_blocking(model_catalog::needs_refresh).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            log::warn!("读取模型清单设置失败: {}", e);
+            return;
+        }
+    }
+
+    // 失败时已记录日志，继续使用当前模型目录
+    if let Ok(status) = model_catalog::refresh_catalog().await {
+        if let Err(e) = app.emit("model-catalog-updated", status) {
+            log::error!("发射模型清单更新事件失败: {}", e);
+        }
+    }
+}
+
+/// 刷新超过 TTL 未查询的密钥余额
+async fn refresh_stale_balances(app: &tauri::AppHandle, settings: &BalanceRefreshSettings) {
+    let ttl = chrono::Duration::minutes(settings.ttl_minutes as i64);
+    let results = match balance::refresh_providers(
+        |p| !balance::is_fresh(p, ttl),
+        balance_progress_emitter(app),
+    )
+    .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            log::error!("定时刷新余额失败: {}", e);
+            return;
+        }
+    };
+    if results.is_empty() {
+        return;
+    }
+
+    log::info!("定时刷新了 {} 个密钥的余额", results.len());
+    let _ = run_blocking(move || {
+        for (provider, result) in &results {
+            audit::record_balance(
+                AuditSource::Auto,
+                AuditTarget::named(Some(provider.id.clone()), provider.name.clone()),
+                result,
+            );
+        }
+        Ok(())
+    })
+    .await;
+
+    notify_quota_alerts(app).await;
+    notify_expiry_alerts(app).await;
+    apply_auto_rotation(app).await;
+    rebuild_tray_menu(app).await;
+}
+
+/// 内部停用密钥函数
+async fn disable_provider_internal(
+    app: &tauri::AppHandle,
+    source: AuditSource,
+) -> Result<(), AppError> {
+    run_blocking(move || {
+        let target = AuditTarget::active_provider();
+        let result = provider_manager::disable_provider();
+        audit::record(AuditAction::DisableProvider, source, target, None, &result);
+        result
+    })
+    .await?;
+    log::info!("已停用密钥");
+
+    // 停用成功后重新创建托盘菜单
+    rebuild_tray_menu(app).await;
+
+    // 发射事件到前端，通知密钥已停用
+    let event_data = serde_json::json!({
+        "providerId": ""
+    });
+    if let Err(e) = app.emit("provider-switched", event_data) {
+        log::error!("发射密钥停用事件失败: {}", e);
+    }
+
+    Ok(())
+}
+
+/// 更新托盘菜单的 Tauri 命令
+#[tauri::command]
+async fn update_tray_menu(app: tauri::AppHandle) -> Result<bool, AppError> {
+    run_blocking(move || {
+        if let Ok(new_menu) = create_tray_menu(&app) {
+            if let Some(tray) = app.tray_by_id("main") {
+                tray.set_menu(Some(new_menu))
+                    .map_err(|e| AppError::other(format!("更新托盘菜单失败: {}", e)))?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    })
+    .await
+}
+
+// ==================== 配置管理命令 ====================
+
+/// 获取应用配置
+#[tauri::command]
+async fn get_config() -> Result<AppConfig, AppError> {
+    run_blocking(provider_manager::load_config_with_secrets).await
+}
+
+/// 添加密钥
+#[tauri::command]
+async fn add_provider(
+    app: tauri::AppHandle,
+    name: String,
+    api_key: String,
+    verify: Option<bool>,
+) -> Result<Provider, AppError> {
+    let result = health::add_provider(name.clone(), api_key, verify.unwrap_or(false)).await;
+    let provider = run_blocking(move || {
+        let provider_id = result.as_ref().ok().map(|p| p.id.clone());
+        audit::record(
+            AuditAction::AddProvider,
+            AuditSource::Ui,
+            AuditTarget::named(provider_id, name),
+            None,
+            &result,
+        );
+        result
+    })
+    .await?;
+
+    // 刷新托盘菜单
+    let _ = update_tray_menu(app).await;
+
+    Ok(provider)
+}
+
+/// 删除密钥
+#[tauri::command]
+async fn remove_provider(app: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    run_blocking(move || {
+        let target = AuditTarget::provider(&id);
+        let result = provider_manager::remove_provider(id);
+        audit::record(
+            AuditAction::RemoveProvider,
+            AuditSource::Ui,
+            target,
+            None,
+            &result,
+        );
+        result
+    })
+    .await?;
+
+    // 刷新托盘菜单
+    let _ = update_tray_menu(app).await;
+
+    Ok(())
+}
+
+/// 切换密钥
+#[tauri::command]
+async fn switch_provider(app: tauri::AppHandle, id: String) -> Result<Provider, AppError> {
+    let provider = run_blocking(move || {
+        let result = provider_manager::switch_provider(id.clone());
+        audit::record(
+            AuditAction::SwitchProvider,
+            AuditSource::Ui,
+            AuditTarget::provider(&id),
+            None,
+            &result,
+        );
+        result
+    })
+    .await?;
+
+    // 刷新托盘菜单
+    let _ = update_tray_menu(app).await;
+
+    Ok(provider)
+}
+
+/// 停用当前密钥
+#[tauri::command]
+async fn disable_provider(app: tauri::AppHandle) -> Result<(), AppError> {
+    run_blocking(|| {
+        let target = AuditTarget::active_provider();
+        let result = provider_manager::disable_provider();
+        audit::record(
+            AuditAction::DisableProvider,
+            AuditSource::Ui,
+            target,
+            None,
+            &result,
+        );
+        result
+    })
+    .await?;
+
+    // 刷新托盘菜单
+    let _ = update_tray_menu(app).await;
+
+    Ok(())
+}
+
+/// 获取当前激活的密钥
+#[tauri::command]
+async fn get_active_provider() -> Result<Option<Provider>, AppError> {
+    run_blocking(provider_manager::get_active_provider).await
+}
+
+// ==================== 导入导出命令 ====================
+
+/// 导出所有密钥到文件（未指定格式时按扩展名判断），返回导出数量
+#[tauri::command]
+async fn export_providers(format: Option<TransferFormat>, path: String) -> Result<usize, AppError> {
+    run_blocking(move || {
+        import_export::export_providers_to_file(format, std::path::Path::new(&path))
+    })
+    .await
+}
+
+/// 从文件导入密钥，返回逐行导入结果
+#[tauri::command]
+async fn import_providers(
+    app: tauri::AppHandle,
+    format: Option<TransferFormat>,
+    path: String,
+) -> Result<ImportReport, AppError> {
+    let report = run_blocking(move || {
+        let report =
+            import_export::import_providers_from_file(format, std::path::Path::new(&path))?;
+        audit::record_import(AuditSource::Ui, &report);
+        Ok(report)
+    })
+    .await?;
+
+    // 刷新托盘菜单
+    if report.added > 0 {
+        let _ = update_tray_menu(app).await;
+    }
+
+    Ok(report)
+}
+
+// ==================== 余额查询命令 ====================
+
+/// 查询单个 API Key 的余额
+#[tauri::command]
+async fn check_balance(api_key: String) -> Result<BalanceInfo, AppError> {
+    run_blocking(provider_manager::ensure_vault_unlocked).await?;
+    balance::check_balance(&api_key).await
+}
+
+/// 批量查询 API Keys 的余额
+#[tauri::command]
+async fn batch_check_balances(
+    api_keys: Vec<String>,
+) -> Result<HashMap<String, BalanceCheckResult>, AppError> {
+    run_blocking(provider_manager::ensure_vault_unlocked).await?;
+    balance::batch_check_balances(api_keys).await
+}
+
+/// 查询密钥余额并更新到配置
+#[tauri::command]
+async fn refresh_provider_balance(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<BalanceInfo, AppError> {
+    // 加载配置获取密钥信息，并从存储后端解析 API Key
+    let provider_id = id.clone();
+    let (target, api_key) = run_blocking(move || {
+        let config = provider_manager::load_config()?;
+        vault::ensure_unlocked(&config)?;
+        let provider = config
+            .providers
+            .iter()
+            .find(|p| p.id == provider_id)
+            .ok_or_else(|| AppError::not_found("密钥", provider_id.clone()))?;
+
+        let target = AuditTarget::named(Some(provider.id.clone()), provider.name.clone());
+        Ok((target, secret_store::resolve_api_key(provider)))
+    })
+    .await?;
+
+    // 查询余额
+    let result = match api_key {
+        Ok(api_key) => balance::check_balance(&api_key).await,
+        Err(e) => Err(e),
+    };
+
+    // 成功和失败都记录到配置
+    let recorded = result.clone();
+    run_blocking(move || {
+        audit::record_balance(AuditSource::Ui, target, &recorded);
+        provider_manager::record_balance_results(&[(id.as_str(), &recorded)])
+    })
+    .await?;
+    let balance = result?;
+    notify_quota_alerts(&app).await;
+    notify_expiry_alerts(&app).await;
+    apply_auto_rotation(&app).await;
+
+    Ok(balance)
+}
+
+/// 批量刷新所有密钥余额（并发查询，最后统一写入配置）
+#[tauri::command]
+async fn refresh_all_balances(app: tauri::AppHandle) -> Result<RefreshAllResult, AppError> {
+    let results = balance::refresh_providers(|_| true, balance_progress_emitter(&app)).await?;
+
+    let errors = run_blocking(move || {
+        let mut errors = HashMap::new();
+        for (provider, result) in results {
+            audit::record_balance(
+                AuditSource::Ui,
+                AuditTarget::named(Some(provider.id.clone()), provider.name.clone()),
+                &result,
+            );
+            if let Err(e) = result {
+                log::warn!("密钥 {} 余额查询失败: {}", provider.name, e);
+                errors.insert(provider.id, e);
+            }
+        }
+        Ok(errors)
+    })
+    .await?;
+
+    notify_quota_alerts(&app).await;
+    notify_expiry_alerts(&app).await;
+    apply_auto_rotation(&app).await;
+    rebuild_tray_menu(&app).await;
+
+    // 重新加载配置返回更新后的密钥列表
+    let providers = run_blocking(provider_manager::load_config_with_secrets)
+        .await?
+        .providers;
+    Ok(RefreshAllResult { providers, errors })
+}
+
+/// 重新校验所有密钥（忽略刷新 TTL），返回每个密钥的状态
+#[tauri::command]
+async fn validate_all_providers(app: tauri::AppHandle) -> Result<Vec<KeyValidation>, AppError> {
+    let validations =
+        health::validate_all_providers(AuditSource::Ui, balance_progress_emitter(&app)).await?;
+
+    notify_quota_alerts(&app).await;
+    notify_expiry_alerts(&app).await;
+    apply_auto_rotation(&app).await;
+    rebuild_tray_menu(&app).await;
+    Ok(validations)
+}
+
+/// 获取批量余额查询设置
+#[tauri::command]
+async fn get_balance_check_settings() -> Result<BalanceCheckSettings, AppError> {
+    run_blocking(balance::get_check_settings).await
+}
+
+/// 保存批量余额查询设置
+#[tauri::command]
+async fn set_balance_check_settings(settings: BalanceCheckSettings) -> Result<(), AppError> {
+    run_blocking(move || balance::set_check_settings(settings)).await
+}
+
+/// 获取后台定时刷新设置
+#[tauri::command]
+async fn get_balance_refresh_settings() -> Result<BalanceRefreshSettings, AppError> {
+    run_blocking(balance::get_refresh_settings).await
+}
+
+/// 保存后台定时刷新设置
+#[tauri::command]
+async fn set_balance_refresh_settings(settings: BalanceRefreshSettings) -> Result<(), AppError> {
+    run_blocking(move || balance::set_refresh_settings(settings)).await
+}
+
+/// 获取到期提醒设置
+#[tauri::command]
+async fn get_expiry_settings() -> Result<ExpirySettings, AppError> {
+    run_blocking(expiry::get_settings).await
+}
+
+/// 保存到期提醒设置
+#[tauri::command]
+async fn set_expiry_settings(settings: ExpirySettings) -> Result<(), AppError> {
+    run_blocking(move || expiry::set_settings(settings)).await
+}
+
+/// 获取额度提醒设置
+#[tauri::command]
+async fn get_quota_alert_settings() -> Result<QuotaAlertSettings, AppError> {
+    run_blocking(quota_alert::get_settings).await
+}
+
+/// 保存额度提醒设置
+#[tauri::command]
+async fn set_quota_alert_settings(settings: QuotaAlertSettings) -> Result<(), AppError> {
+    run_blocking(move || quota_alert::set_settings(settings)).await
+}
+
+/// 设置密钥单独的额度提醒阈值（传空使用全局设置）
+#[tauri::command]
+async fn set_provider_alert_thresholds(
+    id: String,
+    thresholds: Option<Vec<f64>>,
+) -> Result<(), AppError> {
+    run_blocking(move || quota_alert::set_provider_thresholds(id, thresholds)).await
+}
+
+/// 获取密钥的余额历史
+#[tauri::command]
+async fn get_balance_history(
+    provider_id: String,
+    range: Option<HistoryRange>,
+) -> Result<Vec<BalanceSnapshot>, AppError> {
+    run_blocking(move || {
+        balance_history::get_balance_history(&provider_id, &range.unwrap_or_default())
+    })
+    .await
+}
+
+/// 预测密钥的每日消耗量和用完时间
+#[tauri::command]
+async fn get_balance_forecast(provider_id: String) -> Result<BalanceForecast, AppError> {
+    run_blocking(move || balance_history::forecast(&provider_id)).await
+}
+
+/// 获取当前使用的 Factory.ai API 地址
+#[tauri::command]
+async fn get_api_base_url() -> Result<String, AppError> {
+    let config = run_blocking(provider_manager::load_config).await?;
+    Ok(factory_client::resolve_base_url(&config))
+}
+
+/// 设置 Factory.ai API 地址（传空恢复默认地址）
+#[tauri::command]
+async fn set_api_base_url(url: Option<String>) -> Result<(), AppError> {
+    run_blocking(move || provider_manager::set_api_base_url(url)).await
+}
+
+/// 获取网络设置
+#[tauri::command]
+async fn get_network_settings() -> Result<NetworkSettings, AppError> {
+    run_blocking(network::get_settings).await
+}
+
+/// 保存网络设置（代理、证书、超时）
+#[tauri::command]
+async fn set_network_settings(settings: NetworkSettings) -> Result<(), AppError> {
+    run_blocking(move || network::set_settings(settings)).await
+}
+
+/// 测试能否连接 Factory API，失败时报告出错的环节
+#[tauri::command]
+async fn test_connectivity() -> Result<ConnectivityReport, AppError> {
+    network::test_connectivity().await
+}
+
+// ==================== 自动轮换命令 ====================
+
+/// 获取自动轮换策略
+#[tauri::command]
+async fn get_rotation_policy() -> Result<RotationPolicy, AppError> {
+    run_blocking(rotation::get_policy).await
+}
+
+/// 设置自动轮换策略
+#[tauri::command]
+async fn set_rotation_policy(
+    app: tauri::AppHandle,
+    policy: RotationPolicy,
+) -> Result<(), AppError> {
+    run_blocking(move || rotation::set_policy(policy)).await?;
+    // 新策略可能立即触发轮换
+    apply_auto_rotation(&app).await;
+    Ok(())
+}
+
+// ==================== 密钥库命令 ====================
+
+/// 获取密钥库状态
+#[tauri::command]
+async fn get_vault_status() -> Result<VaultStatus, AppError> {
+    run_blocking(provider_manager::get_vault_status).await
+}
+
+/// 启用密钥库（加密现有 API Key）
+#[tauri::command]
+async fn enable_vault(passphrase: String) -> Result<(), AppError> {
+    run_blocking(move || provider_manager::enable_vault(passphrase)).await
+}
+
+/// 解锁密钥库
+#[tauri::command]
+async fn unlock_vault(passphrase: String) -> Result<(), AppError> {
+    run_blocking(move || provider_manager::unlock_vault(passphrase)).await
+}
+
+/// 锁定密钥库
+#[tauri::command]
+async fn lock_vault() -> Result<(), AppError> {
+    provider_manager::lock_vault();
+    Ok(())
+}
+
+/// 停用密钥库（恢复明文保存）
+#[tauri::command]
+async fn disable_vault(passphrase: String) -> Result<(), AppError> {
+    run_blocking(move || provider_manager::disable_vault(passphrase)).await
+}
+
+/// 切换 API Key 存储后端
+#[tauri::command]
+async fn set_secret_backend(backend: SecretBackend) -> Result<(), AppError> {
+    run_blocking(move || provider_manager::set_secret_backend(backend)).await
+}
+
+// ==================== 环境变量管理命令 ====================
+
+/// 获取当前环境变量中的 API Key
+#[tauri::command]
+async fn get_current_api_key() -> Result<Option<String>, AppError> {
+    run_blocking(droid_config::get_factory_api_key_env).await
+}
+
+// ==================== 模型管理命令 ====================
+
+/// 获取所有可用模型
+#[tauri::command]
+async fn get_available_models() -> Result<Vec<ModelInfo>, AppError> {
+    run_blocking(model_manager::get_available_models).await
+}
+
+/// 获取当前选中的模型
+#[tauri::command]
+async fn get_selected_model() -> Result<Option<String>, AppError> {
+    run_blocking(model_manager::get_selected_model).await
+}
+
+/// 设置选中的模型
+#[tauri::command]
+async fn set_selected_model(model_id: String) -> Result<(), AppError> {
+    run_blocking(move || {
+        let result = model_manager::set_selected_model(model_id.clone());
+        audit::record(
+            AuditAction::SetSelectedModel,
+            AuditSource::Ui,
+            AuditTarget::model(&model_id),
+            None,
+            &result,
+        );
+        result
+    })
+    .await
+}
+
+/// 添加自定义模型
+#[tauri::command]
+async fn add_custom_model(
+    id: String,
+    name: String,
+    provider: String,
+    description: Option<String>,
+    reasoning_level: Option<ReasoningLevel>,
+    cost_multiplier: Option<f64>,
+) -> Result<(), AppError> {
+    run_blocking(move || {
+        model_manager::add_custom_model(
+            id,
+            name,
+            provider,
+            description,
+            reasoning_level,
+            cost_multiplier,
+        )
+    })
+    .await
+}
+
+/// 获取密钥剩余额度在各模型下实际可用的 tokens
+#[tauri::command]
+async fn get_model_quotas(provider_id: String) -> Result<Vec<ModelQuota>, AppError> {
+    run_blocking(move || model_manager::get_model_quotas(&provider_id)).await
+}
+
+/// 删除自定义模型
+#[tauri::command]
+async fn remove_custom_model(model_id: String) -> Result<(), AppError> {
+    run_blocking(move || model_manager::remove_custom_model(model_id)).await
+}
+
+/// 设置模型的推理级别
+#[tauri::command]
+async fn set_model_reasoning_level(
+    model_id: String,
+    reasoning_level: ReasoningLevel,
+) -> Result<(), AppError> {
+    run_blocking(move || model_manager::set_model_reasoning_level(model_id, reasoning_level)).await
+}
+
+/// 重置模型配置为默认值
+#[tauri::command]
+async fn reset_models_config() -> Result<(), AppError> {
+    run_blocking(model_manager::reset_models_config).await
+}
+
+/// 获取外部模型清单设置
+#[tauri::command]
+async fn get_model_catalog_settings() -> Result<ModelCatalogSettings, AppError> {
+    run_blocking(model_catalog::get_settings).await
+}
+
+/// 保存外部模型清单设置并立即获取清单
+#[tauri::command]
+async fn set_model_catalog_settings(
+    settings: ModelCatalogSettings,
+) -> Result<ModelCatalogStatus, AppError> {
+    run_blocking(move || model_catalog::set_settings(settings)).await?;
+    model_catalog::refresh_catalog().await
+}
+
+/// 获取当前使用的模型目录
+#[tauri::command]
+async fn get_model_catalog_status() -> Result<ModelCatalogStatus, AppError> {
+    run_blocking(model_catalog::get_status).await
+}
+
+/// 重新获取模型清单
+#[tauri::command]
+async fn refresh_model_catalog() -> Result<ModelCatalogStatus, AppError> {
+    model_catalog::refresh_catalog().await
+}
+
+// ==================== 项目绑定命令 ====================
+
+/// 获取已登记项目的绑定情况
+#[tauri::command]
+async fn get_project_bindings() -> Result<Vec<ProjectBinding>, AppError> {
+    run_blocking(project::list_project_bindings).await
+}
+
+/// 登记项目目录
+#[tauri::command]
+async fn add_project_dir(dir: String) -> Result<ProjectBinding, AppError> {
+    run_blocking(move || project::add_project_dir(dir)).await
+}
+
+/// 取消登记项目目录
+#[tauri::command]
+async fn remove_project_dir(dir: String) -> Result<(), AppError> {
+    run_blocking(move || project::remove_project_dir(dir)).await
+}
+
+// ==================== 审计日志命令 ====================
+
+/// 查询审计日志（按时间倒序）
+#[tauri::command]
+async fn get_audit_log(filter: Option<AuditFilter>) -> Result<Vec<AuditRecord>, AppError> {
+    run_blocking(move || audit::get_audit_log(&filter.unwrap_or_default())).await
+}
+
+// ==================== 其他命令 ====================
+
+/// 测试命令
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // 初始化日志
+    env_logger::init();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            // 创建动态托盘菜单
+            let menu = create_tray_menu(app.handle())?;
+
+            // 构建托盘
+            let app_handle_for_tray = app.handle().clone();
+            let tray_builder = TrayIconBuilder::with_id("main")
+                .on_tray_icon_event(move |_tray, event| match event {
+                    // 左键单击切换窗口显示/隐藏
+                    TrayIconEvent::Click {
+                        button: tauri::tray::MouseButton::Left,
+                        ..
+                    } => {
+                        if let Some(window) = app_handle_for_tray.get_webview_window("main") {
+                            if let Ok(is_visible) = window.is_visible() {
+                                if is_visible {
+                                    let _ = window.hide();
+                                } else {
+                                    let _ = window.unminimize();
+                                    let _ = window.show();
+                                    let _ = window.set_focus();
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                })
+                .menu(&menu)
+                .on_menu_event(|app, event| {
+                    handle_tray_menu_event(app, &event.id.0);
+                })
+                .show_menu_on_left_click(false)
+                .icon(app.default_window_icon().unwrap().clone());
+
+            let _tray = tray_builder.build(app)?;
+
+            // 启动后台定时刷新余额
+            tauri::async_runtime::spawn(run_balance_scheduler(app.handle().clone()));
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            // 配置管理
+            get_config,
+            add_provider,
+            remove_provider,
+            switch_provider,
+            disable_provider,
+            get_active_provider,
+            // 导入导出
+            export_providers,
+            import_providers,
+            // 余额查询
+            check_balance,
+            batch_check_balances,
+            refresh_provider_balance,
+            refresh_all_balances,
+            validate_all_providers,
+            get_balance_history,
+            get_balance_forecast,
+            get_balance_check_settings,
+            set_balance_check_settings,
+            get_balance_refresh_settings,
+            set_balance_refresh_settings,
+            get_quota_alert_settings,
+            set_quota_alert_settings,
+            set_provider_alert_thresholds,
+            get_expiry_settings,
+            set_expiry_settings,
+            get_api_base_url,
+            set_api_base_url,
+            get_network_settings,
+            set_network_settings,
+            test_connectivity,
+            // 自动轮换
+            get_rotation_policy,
+            set_rotation_policy,
+            // 密钥库
+            get_vault_status,
+            enable_vault,
+            unlock_vault,
+            lock_vault,
+            disable_vault,
+            set_secret_backend,
+            // 环境变量
+            get_current_api_key,
+            // 模型管理
+            get_available_models,
+            get_selected_model,
+            set_selected_model,
+            add_custom_model,
+            remove_custom_model,
+            set_model_reasoning_level,
+            reset_models_config,
+            get_model_quotas,
+            get_model_catalog_settings,
+            set_model_catalog_settings,
+            get_model_catalog_status,
+            refresh_model_catalog,
+            // 项目绑定
+            get_project_bindings,
+            add_project_dir,
+            remove_project_dir,
+            // 审计日志
+            get_audit_log,
+            // 托盘菜单
+            update_tray_menu,
+            // 测试
+            greet,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,151 @@
+use crate::audit::{self, AuditTarget};
+use crate::balance;
+use crate::config::run_blocking;
+use crate::error::AppError;
+use crate::expiry;
+use crate::models::{AuditSource, BalanceInfo, KeyValidation, Provider, ProviderStatus};
+use crate::provider_manager::{self, load_config};
+use chrono::Utc;
+
+/// Factory API Key 前缀
+const API_KEY_PREFIX: &str = "fk-";
+
+/// 检查 API Key 格式：fk- 开头，之后不能为空，且不能包含空白、引号等复制时带入的字符
+pub fn validate_key_format(api_key: &str) -> Result<(), AppError> {
+    let Some(body) = api_key.strip_prefix(API_KEY_PREFIX) else {
+        return Err(AppError::InvalidKey(
+            "API Key 格式无效（应以 fk- 开头）".to_string(),
+        ));
+    };
+    if body.is_empty() {
+        return Err(AppError::InvalidKey(
+            "API Key 格式无效（fk- 之后缺少内容）".to_string(),
+        ));
+    }
+    if let Some(c) = body
+        .chars()
+        .find(|c| !c.is_ascii_graphic() || matches!(c, '"' | '\'' | '`'))
+    {
+        return Err(AppError::InvalidKey(format!(
+            "API Key 格式无效（包含非法字符 {:?}）",
+            c
+        )));
+    }
+    Ok(())
+}
+
+/// 根据余额查询结果判断密钥状态，网络错误等无法判断时为空
+pub fn status_from_result(result: &Result<BalanceInfo, AppError>) -> Option<ProviderStatus> {
+    match result {
+        Ok(balance) => Some(status_from_balance(balance)),
+        Err(AppError::Unauthorized { status: 403, .. }) => Some(ProviderStatus::Revoked),
+        Err(AppError::Unauthorized { .. }) => Some(ProviderStatus::Invalid),
+        Err(AppError::Http { status: 402, .. }) => Some(ProviderStatus::Exhausted),
+        Err(_) => None,
+    }
+}
+
+fn status_from_balance(balance: &BalanceInfo) -> ProviderStatus {
+    if expiry::balance_expiry_date(balance).is_some_and(|d| d <= Utc::now()) {
+        ProviderStatus::Expired
+    } else if balance.exceeded {
+        ProviderStatus::Exhausted
+    } else {
+        ProviderStatus::Valid
+    }
+}
+
+/// 状态说明（如“密钥无效”），用于托盘和命令行；未查询和正常时为空
+pub fn status_hint(status: ProviderStatus) -> Option<&'static str> {
+    match status {
+        ProviderStatus::Unknown | ProviderStatus::Valid => None,
+        ProviderStatus::Invalid => Some("密钥无效"),
+        ProviderStatus::Revoked => Some("密钥已吊销"),
+        ProviderStatus::Expired => Some("密钥已过期"),
+        ProviderStatus::Exhausted => Some("配额已用尽"),
+    }
+}
+
+/// 在线校验 API Key：检查格式后查询一次余额
+pub async fn verify_api_key(api_key: &str) -> Result<BalanceInfo, AppError> {
+    validate_key_format(api_key)?;
+    balance::check_balance(api_key).await
+}
+
+/// 添加密钥；verify 为真时先在线校验，校验失败不添加，成功时保存查询到的余额和状态
+pub async fn add_provider(
+    name: String,
+    api_key: String,
+    verify: bool,
+) -> Result<Provider, AppError> {
+    let api_key = api_key.trim().to_string();
+    let checked = if verify {
+        Some(verify_api_key(&api_key).await?)
+    } else {
+        None
+    };
+
+    run_blocking(move || {
+        let provider = provider_manager::add_provider(name, api_key)?;
+        let Some(balance) = checked else {
+            return Ok(provider);
+        };
+        provider_manager::record_balance_results(&[(provider.id.as_str(), &Ok(balance))])?;
+        load_config()?
+            .providers
+            .into_iter()
+            .find(|p| p.id == provider.id)
+            .ok_or_else(|| AppError::not_found("密钥", provider.id))
+    })
+    .await
+}
+
+/// 重新查询所有密钥（忽略刷新 TTL），返回每个密钥更新后的状态
+pub async fn validate_all_providers(
+    source: AuditSource,
+    on_result: impl FnMut(&Provider, &Result<BalanceInfo, AppError>, usize),
+) -> Result<Vec<KeyValidation>, AppError> {
+    let results = balance::refresh_providers(|_| true, on_result).await?;
+    run_blocking(move || summarize_validations(source, results)).await
+}
+
+/// 记录校验结果的审计日志，并读取各密钥更新后的状态
+fn summarize_validations(
+    source: AuditSource,
+    results: Vec<(Provider, Result<BalanceInfo, AppError>)>,
+) -> Result<Vec<KeyValidation>, AppError> {
+    let config = load_config()?;
+
+    let validations: Vec<KeyValidation> = results
+        .into_iter()
+        .map(|(provider, result)| {
+            audit::record_balance(
+                source,
+                AuditTarget::named(Some(provider.id.clone()), provider.name.clone()),
+                &result,
+            );
+            let status = config
+                .providers
+                .iter()
+                .find(|p| p.id == provider.id)
+                .map_or(provider.status, |p| p.status);
+            KeyValidation {
+                provider_id: provider.id,
+                provider_name: provider.name,
+                status,
+                error: result.err(),
+            }
+        })
+        .collect();
+
+    let invalid = validations
+        .iter()
+        .filter(|v| matches!(v.status, ProviderStatus::Invalid | ProviderStatus::Revoked))
+        .count();
+    log::info!(
+        "已校验 {} 个密钥，{} 个无效或已吊销",
+        validations.len(),
+        invalid
+    );
+    Ok(validations)
+}
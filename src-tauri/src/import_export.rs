@@ -0,0 +1,524 @@
+use crate::config::atomic_write;
+use crate::error::AppError;
+use crate::health;
+use crate::models::{
+    AppConfig, ImportReport, ImportRow, ImportStatus, Provider, ProviderStatus, QuotaAlertState,
+    TransferFormat,
+};
+use crate::provider_manager::{
+    check_duplicate, delete_secrets, load_config, resolve_known_api_keys, update_config,
+    validate_name,
+};
+use crate::secret_store::{self, resolve_api_key};
+use crate::vault;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// dotenv 中记录原始密钥名称的注释前缀（变量名无法表示原名称时写在变量上一行）
+const DOTENV_NAME_COMMENT: &str = "# name: ";
+
+/// CSV 中的一行
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRecord {
+    name: String,
+    api_key: String,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// 待导入的密钥
+struct ImportEntry {
+    line: usize,
+    name: String,
+    api_key: String,
+    notes: Option<String>,
+    /// JSON 导入时保留的原始信息
+    source: Option<Provider>,
+    /// 解析失败原因
+    error: Option<AppError>,
+}
+
+impl ImportEntry {
+    fn new(line: usize, name: String, api_key: String) -> Self {
+        Self {
+            line,
+            name,
+            api_key,
+            notes: None,
+            source: None,
+            error: None,
+        }
+    }
+
+    fn with_source(self, source: Provider) -> Self {
+        Self {
+            source: Some(source),
+            ..self
+        }
+    }
+
+    fn invalid(line: usize, error: AppError) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::new(line, String::new(), String::new())
+        }
+    }
+}
+
+/// 根据文件扩展名推断格式（.json / .csv / .env）
+pub fn format_from_path(path: &Path) -> Option<TransferFormat> {
+    let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+    match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+        Some(ext) if ext == "json" => Some(TransferFormat::Json),
+        Some(ext) if ext == "csv" => Some(TransferFormat::Csv),
+        Some(ext) if ext == "env" => Some(TransferFormat::Dotenv),
+        _ if file_name.starts_with(".env") => Some(TransferFormat::Dotenv),
+        _ => None,
+    }
+}
+
+// ==================== 导出 ====================
+
+/// 导出所有密钥（包含明文 API Key）
+pub fn export_providers(format: TransferFormat) -> Result<String, AppError> {
+    let providers = portable_providers()?;
+    render(format, &providers)
+}
+
+/// 导出所有密钥到文件，返回导出的数量
+pub fn export_providers_to_file(
+    format: Option<TransferFormat>,
+    path: &Path,
+) -> Result<usize, AppError> {
+    let format = format.or_else(|| format_from_path(path)).ok_or_else(|| {
+        AppError::invalid_input(format!("无法根据文件名判断导出格式: {}", path.display()))
+    })?;
+    let providers = portable_providers()?;
+    atomic_write(path, render(format, &providers)?.as_bytes())?;
+
+    log::info!("已导出 {} 个密钥到 {}", providers.len(), path.display());
+    Ok(providers.len())
+}
+
+fn render(format: TransferFormat, providers: &[Provider]) -> Result<String, AppError> {
+    match format {
+        TransferFormat::Json => serde_json::to_string_pretty(providers)
+            .map_err(|e| AppError::parse("序列化 JSON 失败", e)),
+        TransferFormat::Csv => export_csv(providers),
+        TransferFormat::Dotenv => Ok(export_dotenv(providers)),
+    }
+}
+
+/// 解析出 API Key 并去掉本机存储相关字段，便于在其他机器上导入
+fn portable_providers() -> Result<Vec<Provider>, AppError> {
+    let config = load_config()?;
+    vault::ensure_unlocked(&config)?;
+    config
+        .providers
+        .iter()
+        .map(|p| {
+            let mut provider = p.clone();
+            provider.api_key = resolve_api_key(p)?;
+            provider.encrypted_api_key = None;
+            provider.secret_ref = None;
+            provider.is_active = false;
+            Ok(provider)
+        })
+        .collect()
+}
+
+fn export_csv(providers: &[Provider]) -> Result<String, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for provider in providers {
+        writer
+            .serialize(CsvRecord {
+                name: provider.name.clone(),
+                api_key: provider.api_key.clone(),
+                notes: provider.notes.clone(),
+            })
+            .map_err(|e| AppError::io("写入 CSV 失败", e))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| AppError::io("写入 CSV 失败", e))?;
+    String::from_utf8(data).map_err(|e| AppError::io("写入 CSV 失败", e))
+}
+
+/// 生成 dotenv 内容，名称转换为环境变量格式（非字母数字替换为下划线）
+///
+/// 转换后与原名称不同时，在上一行用注释保留原名称，导入时据此恢复；
+/// 转换后重名的变量追加序号。
+fn export_dotenv(providers: &[Provider]) -> String {
+    let mut used = HashSet::new();
+    let mut out = String::new();
+    for provider in providers {
+        let base = dotenv_name(&provider.name);
+        let mut var = base.clone();
+        let mut n = 2;
+        while !used.insert(var.clone()) {
+            var = format!("{}_{}", base, n);
+            n += 1;
+        }
+        if var != provider.name {
+            out.push_str(&format!("{}{}\n", DOTENV_NAME_COMMENT, provider.name));
+        }
+        out.push_str(&format!("{}={}\n", var, provider.api_key));
+    }
+    out
+}
+
+fn dotenv_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "DROID".to_string()
+    } else {
+        name
+    }
+}
+
+// ==================== 导入 ====================
+
+/// 导入密钥，按 add_provider 的规则去重，返回逐行结果
+pub fn import_providers(format: TransferFormat, content: &str) -> Result<ImportReport, AppError> {
+    let entries = match format {
+        TransferFormat::Json => parse_json(content)?,
+        TransferFormat::Csv => parse_csv(content),
+        TransferFormat::Dotenv => parse_dotenv(content),
+    };
+
+    let known_keys = resolve_known_api_keys()?;
+    // 已写入存储后端的密钥；导入失败（配置未保存）时需要清理
+    let mut stored = Vec::new();
+    let result = update_config(|config| {
+        vault::ensure_unlocked(config)?;
+
+        let mut report = ImportReport::default();
+        for entry in entries {
+            let row = import_entry(config, &known_keys, entry)?;
+            match row.status {
+                ImportStatus::Added => {
+                    report.added += 1;
+                    stored.extend(config.providers.last().cloned());
+                }
+                ImportStatus::SkippedDuplicate => report.skipped += 1,
+                ImportStatus::Invalid => report.invalid += 1,
+            }
+            report.rows.push(row);
+        }
+        Ok(report)
+    });
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            delete_secrets(&stored);
+            return Err(e);
+        }
+    };
+
+    log::info!(
+        "导入完成：新增 {}，跳过 {}，无效 {}",
+        report.added,
+        report.skipped,
+        report.invalid
+    );
+    Ok(report)
+}
+
+/// 从文件导入密钥
+pub fn import_providers_from_file(
+    format: Option<TransferFormat>,
+    path: &Path,
+) -> Result<ImportReport, AppError> {
+    let format = format.or_else(|| format_from_path(path)).ok_or_else(|| {
+        AppError::invalid_input(format!("无法根据文件名判断导入格式: {}", path.display()))
+    })?;
+    let content = fs::read_to_string(path)
+        .map_err(|e| AppError::io(&format!("读取 {} 失败", path.display()), e))?;
+    import_providers(format, &content)
+}
+
+/// 导入单个密钥；只有存储后端写入失败才返回错误（整个导入回滚）
+fn import_entry(
+    config: &mut AppConfig,
+    known_keys: &HashMap<String, String>,
+    entry: ImportEntry,
+) -> Result<ImportRow, AppError> {
+    let mut row = ImportRow {
+        line: entry.line,
+        name: entry.name.clone(),
+        status: ImportStatus::Invalid,
+        provider_id: None,
+        message: None,
+    };
+
+    if let Err(e) = entry
+        .error
+        .clone()
+        .map_or_else(|| validate_entry(&entry), Err)
+    {
+        row.message = Some(e.to_string());
+        return Ok(row);
+    }
+    if let Err(e) = check_duplicate(config, known_keys, &entry.name, &entry.api_key) {
+        row.status = ImportStatus::SkippedDuplicate;
+        row.message = Some(e.to_string());
+        return Ok(row);
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let mut provider = match entry.source {
+        // 保留 JSON 中的 ID（未被占用时）、余额、状态和创建时间
+        Some(source) => Provider {
+            id: if source.id.is_empty() || config.providers.iter().any(|p| p.id == source.id) {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                source.id
+            },
+            balance: source.balance,
+            status: source.status,
+            created_at: source.created_at.or_else(|| Some(now.clone())),
+            ..new_provider(entry.name, entry.notes, &now)
+        },
+        None => new_provider(entry.name, entry.notes, &now),
+    };
+
+    secret_store::store_for_backend(config.secret_backend).put(&mut provider, &entry.api_key)?;
+
+    row.status = ImportStatus::Added;
+    row.provider_id = Some(provider.id.clone());
+    config.providers.push(provider);
+    Ok(row)
+}
+
+fn new_provider(name: String, notes: Option<String>, now: &str) -> Provider {
+    Provider {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        notes,
+        api_key: String::new(),
+        encrypted_api_key: None,
+        secret_ref: None,
+        balance: None,
+        balance_checked_at: None,
+        balance_stale: false,
+        last_error: None,
+        last_error_at: None,
+        consecutive_failures: 0,
+        is_active: false,
+        expired: false,
+        status: ProviderStatus::Unknown,
+        created_at: Some(now.to_string()),
+        updated_at: Some(now.to_string()),
+        alert_thresholds: None,
+        alert_state: QuotaAlertState::default(),
+    }
+}
+
+fn validate_entry(entry: &ImportEntry) -> Result<(), AppError> {
+    validate_name(&entry.name)?;
+    health::validate_key_format(&entry.api_key)
+}
+
+/// 逐项解析 JSON 数组，单项格式错误只标记该行无效
+fn parse_json(content: &str) -> Result<Vec<ImportEntry>, AppError> {
+    let items: Vec<Value> =
+        serde_json::from_str(content).map_err(|e| AppError::parse("解析 JSON 失败", e))?;
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| parse_json_item(i + 1, item))
+        .collect())
+}
+
+fn parse_json_item(line: usize, mut item: Value) -> ImportEntry {
+    let name = item
+        .get("name")
+        .and_then(Value::as_str)
+        .map(|n| n.trim().to_string())
+        .unwrap_or_default();
+    let Some(obj) = item.as_object_mut() else {
+        return ImportEntry::invalid(line, AppError::Parse("不是 JSON 对象".to_string()));
+    };
+    // 手写的文件可以不带 ID，导入时生成
+    obj.entry("id").or_insert_with(|| Value::from(""));
+
+    match serde_json::from_value::<Provider>(item) {
+        Ok(p) => ImportEntry {
+            notes: p.notes.clone(),
+            ..ImportEntry::new(line, name, p.api_key.trim().to_string())
+        }
+        .with_source(p),
+        Err(e) => ImportEntry {
+            name,
+            ..ImportEntry::invalid(line, AppError::parse("解析 JSON 失败", e))
+        },
+    }
+}
+
+fn parse_csv(content: &str) -> Vec<ImportEntry> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    reader
+        .deserialize::<CsvRecord>()
+        .enumerate()
+        .map(|(i, record)| {
+            // 第 1 行是表头
+            let line = i + 2;
+            match record {
+                Ok(r) => ImportEntry {
+                    notes: r.notes.filter(|n| !n.is_empty()),
+                    ..ImportEntry::new(line, r.name, r.api_key)
+                },
+                Err(e) => ImportEntry::invalid(line, AppError::parse("解析 CSV 失败", e)),
+            }
+        })
+        .collect()
+}
+
+fn parse_dotenv(content: &str) -> Vec<ImportEntry> {
+    let mut entries = Vec::new();
+    // 上一行注释中记录的原始名称
+    let mut original_name: Option<String> = None;
+    for (i, line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix(DOTENV_NAME_COMMENT.trim_end()) {
+            original_name = Some(name.trim().to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let name_override = original_name.take();
+        let line = line.strip_prefix("export ").unwrap_or(line).trim();
+        let Some((name, value)) = line.split_once('=') else {
+            entries.push(ImportEntry::invalid(
+                line_no,
+                AppError::Parse("缺少 '='".to_string()),
+            ));
+            continue;
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+        entries.push(ImportEntry::new(
+            line_no,
+            name_override.unwrap_or_else(|| name.trim().to_string()),
+            value.to_string(),
+        ));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(name: &str, api_key: &str) -> Provider {
+        Provider {
+            api_key: api_key.to_string(),
+            ..new_provider(name.to_string(), None, "2026-01-01T00:00:00+00:00")
+        }
+    }
+
+    #[test]
+    fn malformed_json_rows_are_invalid_individually() {
+        let content = r#"[
+            { "name": "好的", "apiKey": "fk-good" },
+            { "name": "坏的", "apiKey": 42 },
+            "not an object",
+            { "id": "kept", "name": "保留 ID", "apiKey": "fk-kept", "notes": "备注" }
+        ]"#;
+        let entries = parse_json(content).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        assert!(entries[0].error.is_none());
+        assert_eq!(entries[0].name, "好的");
+        assert_eq!(entries[0].source.as_ref().unwrap().id, "");
+
+        assert!(entries[1].error.is_some());
+        assert_eq!(entries[1].name, "坏的");
+        assert!(entries[2].error.is_some());
+
+        assert_eq!(entries[3].line, 4);
+        assert_eq!(entries[3].notes.as_deref(), Some("备注"));
+        assert_eq!(entries[3].source.as_ref().unwrap().id, "kept");
+    }
+
+    #[test]
+    fn json_that_is_not_an_array_fails() {
+        assert!(parse_json("{}").is_err());
+    }
+
+    #[test]
+    fn dotenv_round_trip_keeps_names() {
+        let providers = [
+            provider("主账", "fk-1"),
+            provider("备用", "fk-2"),
+            provider("PROD", "fk-3"),
+            provider("my key", "fk-4"),
+        ];
+        let content = export_dotenv(&providers);
+        assert!(content.contains("PROD=fk-3\n"));
+        assert!(!content.contains("# name: PROD"));
+
+        let entries = parse_dotenv(&content);
+        let imported: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.api_key.as_str()))
+            .collect();
+        assert_eq!(
+            imported,
+            [
+                ("主账", "fk-1"),
+                ("备用", "fk-2"),
+                ("PROD", "fk-3"),
+                ("my key", "fk-4"),
+            ]
+        );
+
+        // 变量名不重复
+        let vars: HashSet<&str> = content
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| l.split_once('=').map(|(k, _)| k))
+            .collect();
+        assert_eq!(vars.len(), 4);
+    }
+
+    #[test]
+    fn dotenv_parses_plain_files() {
+        let entries = parse_dotenv("# 注释\nexport A=\"fk-a\"\n\nB='fk-b'\nbroken\n");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            (entries[0].name.as_str(), entries[0].api_key.as_str()),
+            ("A", "fk-a")
+        );
+        assert_eq!(
+            (entries[1].name.as_str(), entries[1].api_key.as_str()),
+            ("B", "fk-b")
+        );
+        assert_eq!(entries[2].line, 5);
+        assert!(entries[2].error.is_some());
+    }
+
+    #[test]
+    fn long_names_are_rejected() {
+        let entry = ImportEntry::new(1, "名".repeat(51), "fk-valid-key".to_string());
+        assert!(validate_entry(&entry).is_err());
+    }
+}
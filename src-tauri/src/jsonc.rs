@@ -0,0 +1,751 @@
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
+
+/// JSONC 解析错误（包含出错的行列号）
+#[derive(Debug, Clone)]
+pub struct JsoncError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsoncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "第 {} 行第 {} 列: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for JsoncError {}
+
+/// 顶层对象的成员
+struct Member {
+    key: String,
+    /// 键在原文中的位置
+    key_range: Range<usize>,
+    /// 值在原文中的位置
+    value: Range<usize>,
+    /// 成员之后的逗号位置
+    comma: Option<usize>,
+}
+
+/// 顶层对象的结构
+struct ObjectLayout {
+    members: Vec<Member>,
+    /// 右花括号的位置
+    close: usize,
+}
+
+impl ObjectLayout {
+    /// 最后一个成员之后的逗号（尾随逗号）位置
+    fn trailing_comma(&self) -> Option<usize> {
+        self.members.last().and_then(|m| m.comma)
+    }
+}
+
+/// 按 JSONC/JSON5 语法扫描文本：允许注释、尾随逗号、单引号字符串、不加引号的键名，
+/// 以及十六进制、带正号、以小数点开头或结尾的数字
+struct Scanner<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> JsoncError {
+        let before = &self.text[..self.pos.min(self.text.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        JsoncError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    /// 跳过空白和注释
+    fn skip_trivia(&mut self) -> Result<(), JsoncError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b' ' | b'\t' | b'\n' | b'\r'), _) => self.pos += 1,
+                (Some(c), _) if !c.is_ascii() => match self.text[self.pos..].chars().next() {
+                    Some(c) if c.is_whitespace() || c == '\u{feff}' => self.pos += c.len_utf8(),
+                    _ => return Ok(()),
+                },
+                (Some(b'/'), Some(b'/')) => {
+                    while self.peek().is_some_and(|c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                (Some(b'/'), Some(b'*')) => match self.text[self.pos + 2..].find("*/") {
+                    Some(i) => self.pos += i + 4,
+                    None => return Err(self.error("块注释未结束")),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8, message: &str) -> Result<(), JsoncError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// 读取单引号或双引号字符串并解码转义字符
+    fn string(&mut self) -> Result<String, JsoncError> {
+        let quote = match self.peek() {
+            Some(c @ (b'"' | b'\'')) => c as char,
+            _ => return Err(self.error("缺少字符串")),
+        };
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.text[self.pos..].chars().next() else {
+                return Err(self.error("字符串未结束"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                c if c == quote => return Ok(out),
+                '\\' => out.extend(self.escape()?),
+                '\n' | '\r' => return Err(self.error("字符串中不能换行")),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// 解码转义字符；转义的换行（续行）不产生字符
+    fn escape(&mut self) -> Result<Option<char>, JsoncError> {
+        let Some(c) = self.text[self.pos..].chars().next() else {
+            return Err(self.error("字符串未结束"));
+        };
+        self.pos += c.len_utf8();
+        Ok(Some(match c {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\u{b}',
+            '0' if !self.peek().is_some_and(|c| c.is_ascii_digit()) => '\0',
+            'x' => {
+                let code = self.hex(2)?;
+                char::from_u32(code).ok_or_else(|| self.error("无效的 Unicode 转义"))?
+            }
+            'u' => {
+                let high = self.hex(4)?;
+                // 代理对由两个 \u 转义组成
+                if (0xD800..0xDC00).contains(&high) && self.text[self.pos..].starts_with("\\u") {
+                    self.pos += 2;
+                    let low = self.hex(4)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("无效的 Unicode 转义"));
+                    }
+                    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                    char::from_u32(code).ok_or_else(|| self.error("无效的 Unicode 转义"))?
+                } else {
+                    char::from_u32(high).ok_or_else(|| self.error("无效的 Unicode 转义"))?
+                }
+            }
+            '\r' => {
+                if self.peek() == Some(b'\n') {
+                    self.pos += 1;
+                }
+                return Ok(None);
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => return Ok(None),
+            c if c.is_ascii_digit() => return Err(self.error("无效的转义字符")),
+            // 其他字符（包括引号和反斜杠）转义后为其本身
+            c => c,
+        }))
+    }
+
+    fn hex(&mut self, len: usize) -> Result<u32, JsoncError> {
+        let value = self
+            .text
+            .get(self.pos..self.pos + len)
+            .filter(|digits| digits.bytes().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("无效的 Unicode 转义"))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// 读取键名：字符串或不加引号的标识符
+    fn key(&mut self) -> Result<String, JsoncError> {
+        if matches!(self.peek(), Some(b'"' | b'\'')) {
+            return self.string();
+        }
+        let len: usize = self.text[self.pos..]
+            .chars()
+            .enumerate()
+            .take_while(|&(i, c)| {
+                c == '$'
+                    || c == '_'
+                    || if i == 0 {
+                        c.is_alphabetic()
+                    } else {
+                        c.is_alphanumeric()
+                    }
+            })
+            .map(|(_, c)| c.len_utf8())
+            .sum();
+        if len == 0 {
+            return Err(self.error("缺少键名"));
+        }
+        self.pos += len;
+        Ok(self.text[self.pos - len..self.pos].to_string())
+    }
+
+    /// 读取一个值
+    fn value(&mut self) -> Result<Value, JsoncError> {
+        match self.peek() {
+            Some(b'{') => {
+                let members = self.members()?;
+                Ok(Value::Object(
+                    members.into_iter().map(|(m, v)| (m.key, v)).collect(),
+                ))
+            }
+            Some(b'[') => self.array(),
+            Some(b'"' | b'\'') => self.string().map(Value::String),
+            Some(_) => self.scalar(),
+            None => Err(self.error("缺少值")),
+        }
+    }
+
+    /// 读取数字、true、false 或 null
+    fn scalar(&mut self) -> Result<Value, JsoncError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, b'+' | b'-' | b'.'))
+        {
+            self.pos += 1;
+        }
+        let value = match &self.text[start..self.pos] {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            "null" => Some(Value::Null),
+            token => parse_number(token),
+        };
+        value.ok_or_else(|| {
+            self.pos = start;
+            self.error("无效的值")
+        })
+    }
+
+    /// 读取数组（允许尾随逗号）
+    fn array(&mut self) -> Result<Value, JsoncError> {
+        self.expect(b'[', "缺少左方括号")?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                None => return Err(self.error("缺少右方括号")),
+                _ => {}
+            }
+            items.push(self.value()?);
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("缺少逗号")),
+            }
+        }
+    }
+
+    /// 读取对象的各个成员（允许尾随逗号），结束时位于右花括号之后
+    fn members(&mut self) -> Result<Vec<(Member, Value)>, JsoncError> {
+        self.expect(b'{', "缺少左花括号")?;
+        let mut members = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(members);
+                }
+                None => return Err(self.error("缺少右花括号")),
+                _ => {}
+            }
+
+            let key_start = self.pos;
+            let key = self.key()?;
+            let key_range = key_start..self.pos;
+            self.skip_trivia()?;
+            self.expect(b':', "缺少冒号")?;
+            self.skip_trivia()?;
+            let value_start = self.pos;
+            let value = self.value()?;
+            let value_range = value_start..self.pos;
+
+            self.skip_trivia()?;
+            let comma = match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    Some(self.pos - 1)
+                }
+                Some(b'}') => None,
+                _ => return Err(self.error("缺少逗号")),
+            };
+            let member = Member {
+                key,
+                key_range,
+                value: value_range,
+                comma,
+            };
+            members.push((member, value));
+        }
+    }
+
+    /// 确认之后只剩空白和注释
+    fn finish(&mut self) -> Result<(), JsoncError> {
+        self.skip_trivia()?;
+        if self.pos < self.text.len() {
+            return Err(self.error("值之后有多余内容"));
+        }
+        Ok(())
+    }
+
+    /// 扫描顶层对象，记录各成员的位置
+    fn object_layout(&mut self) -> Result<ObjectLayout, JsoncError> {
+        self.skip_trivia()?;
+        if self.peek() != Some(b'{') {
+            return Err(self.error("顶层必须是对象"));
+        }
+        let members = self.members()?.into_iter().map(|(m, _)| m).collect();
+        let close = self.pos - 1;
+        self.finish()?;
+        Ok(ObjectLayout { members, close })
+    }
+}
+
+/// 按 JSON5 规则解析数字；Infinity 和 NaN 无法用 JSON 表示，读取为 null
+fn parse_number(token: &str) -> Option<Value> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token.strip_prefix('+').unwrap_or(token)),
+    };
+    if digits == "Infinity" || digits == "NaN" {
+        return Some(Value::Null);
+    }
+
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        if hex.is_empty() || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let n = u64::from_str_radix(hex, 16).ok()?;
+        return if negative {
+            i64::try_from(n).ok().map(|n| Value::from(-n))
+        } else {
+            Some(Value::from(n))
+        };
+    }
+
+    if !matches!(
+        digits.as_bytes(),
+        [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..]
+    ) {
+        return None;
+    }
+    // 补全以小数点开头或结尾的数字（如 .5、5.、5.e3），其余按 JSON 规则解析
+    let mut number = digits.replace(".e", ".0e").replace(".E", ".0E");
+    if number.starts_with('.') {
+        number.insert(0, '0');
+    }
+    if number.ends_with('.') {
+        number.push('0');
+    }
+    if negative {
+        number.insert(0, '-');
+    }
+    serde_json::from_str::<Value>(&number)
+        .ok()
+        .filter(Value::is_number)
+}
+
+/// 解析 JSONC/JSON5 文本（允许注释、尾随逗号、单引号字符串和不加引号的键名）
+///
+/// 重复的键以最后一个为准。
+pub fn parse(text: &str) -> Result<Value, JsoncError> {
+    let mut scanner = Scanner::new(text);
+    scanner.skip_trivia()?;
+    let value = scanner.value()?;
+    scanner.finish()?;
+    Ok(value)
+}
+
+/// 修改 JSONC 顶层对象中的一个值，只改动该值所在的文本，保留注释、键顺序和尾随逗号
+///
+/// 键不存在时添加到对象末尾（沿用上一个成员的缩进和换行风格）。文本无法解析时返回错误，不做修改。
+pub fn set_value(text: &str, key: &str, value: &Value) -> Result<String, JsoncError> {
+    let layout = Scanner::new(text).object_layout()?;
+    let new_value = serde_json::to_string(value).unwrap_or_default();
+    let quoted_key = serde_json::to_string(key).unwrap_or_default();
+
+    // 重复的键以最后一个为准
+    if let Some(member) = layout.members.iter().rev().find(|m| m.key == key) {
+        let mut edits = Vec::new();
+        // 单引号或不加引号的键名改为双引号形式，便于其他工具按 JSON 格式查找
+        if !text[member.key_range.clone()].starts_with('"') {
+            edits.push((member.key_range.clone(), quoted_key));
+        }
+        edits.push((member.value.clone(), new_value));
+        return Ok(splice(text, &edits));
+    }
+
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let entry = format!("{}: {}", quoted_key, new_value);
+    let Some(last) = layout.members.last() else {
+        let close = layout.close;
+        let prefix = if text[..close].ends_with('\n') {
+            ""
+        } else {
+            newline
+        };
+        return Ok(splice(
+            text,
+            &[(close..close, format!("{}  {}{}", prefix, entry, newline))],
+        ));
+    };
+
+    let indent = line_indent(text, last.key_range.start);
+    let anchor = layout.trailing_comma().map_or(last.value.end, |c| c + 1);
+    // 单行对象直接追加在同一行
+    if !text[last.value.end..layout.close].contains('\n') {
+        let edit = match layout.trailing_comma() {
+            Some(_) => (anchor..anchor, format!(" {},", entry)),
+            None => (anchor..anchor, format!(", {}", entry)),
+        };
+        return Ok(splice(text, &[edit]));
+    }
+
+    // 新成员放在上一个成员所在行（包括行尾注释）之后
+    let line_end = end_of_line(text, anchor, layout.close);
+    let edits = match layout.trailing_comma() {
+        Some(_) => vec![(
+            line_end..line_end,
+            format!("{}{}{},", newline, indent, entry),
+        )],
+        None => vec![
+            (last.value.end..last.value.end, ",".to_string()),
+            (
+                line_end..line_end,
+                format!("{}{}{}", newline, indent, entry),
+            ),
+        ],
+    };
+    Ok(splice(text, &edits))
+}
+
+/// 删除 JSONC 顶层对象中的一个键（包括重复的键），只删除该成员所在的文本，保留其余内容
+///
+/// 键不存在时原样返回。文本无法解析时返回错误。
+pub fn remove_value(text: &str, key: &str) -> Result<String, JsoncError> {
+    let mut text = text.to_string();
+    loop {
+        let layout = Scanner::new(&text).object_layout()?;
+        let Some(index) = layout.members.iter().position(|m| m.key == key) else {
+            return Ok(text);
+        };
+        text = splice(&text, &removal(&text, &layout, index));
+    }
+}
+
+/// 删除第 index 个成员所需的修改
+fn removal(text: &str, layout: &ObjectLayout, index: usize) -> Vec<(Range<usize>, String)> {
+    let member = &layout.members[index];
+    let previous = index.checked_sub(1).map(|i| &layout.members[i]);
+    let start = member.key_range.start;
+    let end = member.comma.map_or(member.value.end, |c| c + 1);
+
+    // 成员独占一行（或多行）时删除整行，包括行尾注释
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = end_of_line(text, end, layout.close);
+    if text[line_start..start].trim().is_empty()
+        && matches!(text.as_bytes().get(line_end), Some(b'\r' | b'\n'))
+    {
+        let line_end = line_end
+            + if text[line_end..].starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+        let mut edits = Vec::new();
+        // 删除最后一个成员时去掉上一个成员之后的逗号，避免留下尾随逗号
+        if let (None, Some(comma)) = (member.comma, previous.and_then(|p| p.comma)) {
+            edits.push((comma..comma + 1, String::new()));
+        }
+        edits.push((line_start..line_end, String::new()));
+        return edits;
+    }
+
+    let range = match (member.comma, previous) {
+        (Some(_), _) => start..text.len() - text[end..].trim_start_matches([' ', '\t']).len(),
+        (None, Some(previous)) => previous.value.end..member.value.end,
+        (None, None) => start..member.value.end,
+    };
+    vec![(range, String::new())]
+}
+
+/// 按位置替换文本（各位置互不重叠，按从前到后的顺序给出）
+fn splice(text: &str, edits: &[(Range<usize>, String)]) -> String {
+    let mut result = String::with_capacity(text.len() + 64);
+    let mut last = 0;
+    for (range, replacement) in edits {
+        result.push_str(&text[last..range.start]);
+        result.push_str(replacement);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// pos 所在行开头的缩进
+fn line_indent(text: &str, pos: usize) -> String {
+    let line_start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    text[line_start..pos]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// 从 pos 开始跳过同一行的空白和注释，返回行尾（换行符）位置；遇到其他内容时返回 limit
+fn end_of_line(text: &str, pos: usize, limit: usize) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = pos;
+    while i < limit {
+        match bytes[i] {
+            b' ' | b'\t' => i += 1,
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => return i,
+            b'\n' => return i,
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                return text[i..limit].find('\n').map_or(limit, |n| {
+                    let end = i + n;
+                    if end > 0 && bytes[end - 1] == b'\r' {
+                        end - 1
+                    } else {
+                        end
+                    }
+                });
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => match text[i + 2..limit].find("*/") {
+                Some(n) => i += n + 4,
+                None => return limit,
+            },
+            _ => return limit,
+        }
+    }
+    limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_comments_and_trailing_commas() {
+        let text = r#"{
+  // 行注释
+  "model": "claude", /* 块注释 */
+  "list": [1, 2, 3,],
+  "nested": { "a": { "b": true, }, },
+}"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "model": "claude",
+                "list": [1, 2, 3],
+                "nested": { "a": { "b": true } },
+            })
+        );
+    }
+
+    #[test]
+    fn parses_json5_syntax() {
+        let text = "{unquoted: 'single', hex: 0xFF, plus: +1, lead: .5, trail: 5., inf: Infinity}";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "unquoted": "single",
+                "hex": 255,
+                "plus": 1,
+                "lead": 0.5,
+                "trail": 5.0,
+                "inf": null,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let value =
+            parse(r#"{"q": "say \"hi\" \\ \n", "s": 'it\'s', "u": "\u4e2d\ud83d\ude00"}"#).unwrap();
+        assert_eq!(value["q"], "say \"hi\" \\ \n");
+        assert_eq!(value["s"], "it's");
+        assert_eq!(value["u"], "中😀");
+    }
+
+    #[test]
+    fn rejects_invalid_surrogate_pairs() {
+        for text in [
+            r#""\ud800\u0041""#,
+            r#""\ud800A""#,
+            r#""\ud800""#,
+            r#""\udc00""#,
+        ] {
+            let err = parse(text).unwrap_err();
+            assert_eq!(err.message, "无效的 Unicode 转义", "{}", text);
+        }
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let err = parse("{\r\n  \"a\": 1\r\n  \"b\": 2\r\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        assert!(parse("{\"a\": 1} extra").is_err());
+        assert!(parse("{\"a\": /* 未结束").is_err());
+    }
+
+    #[test]
+    fn set_value_replaces_only_the_value() {
+        let text =
+            "{\n  // 默认模型\n  \"model\": \"old\", // 行尾注释\n  \"other\": [1, 2,],\n}\n";
+        let edited = set_value(text, "model", &json!("new")).unwrap();
+        assert_eq!(
+            edited,
+            "{\n  // 默认模型\n  \"model\": \"new\", // 行尾注释\n  \"other\": [1, 2,],\n}\n"
+        );
+    }
+
+    #[test]
+    fn set_value_quotes_json5_keys() {
+        let edited = set_value("{model: 'old'}", "model", &json!("new")).unwrap();
+        assert_eq!(edited, "{\"model\": \"new\"}");
+    }
+
+    #[test]
+    fn set_value_appends_missing_key() {
+        let text = "{\n  \"a\": 1 // 注释\n}\n";
+        assert_eq!(
+            set_value(text, "b", &json!(true)).unwrap(),
+            "{\n  \"a\": 1, // 注释\n  \"b\": true\n}\n"
+        );
+
+        let trailing = "{\n    \"a\": 1,\n}";
+        assert_eq!(
+            set_value(trailing, "b", &json!(2)).unwrap(),
+            "{\n    \"a\": 1,\n    \"b\": 2,\n}"
+        );
+
+        assert_eq!(
+            set_value("{\"a\": 1}", "b", &json!(2)).unwrap(),
+            "{\"a\": 1, \"b\": 2}"
+        );
+        assert_eq!(set_value("{}", "b", &json!(2)).unwrap(), "{\n  \"b\": 2\n}");
+    }
+
+    #[test]
+    fn set_value_keeps_crlf_line_endings() {
+        let text = "{\r\n  \"a\": 1\r\n}\r\n";
+        assert_eq!(
+            set_value(text, "b", &json!({ "c": 1 })).unwrap(),
+            "{\r\n  \"a\": 1,\r\n  \"b\": {\"c\":1}\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn set_value_edits_the_last_duplicate() {
+        let edited = set_value("{\"a\": 1, \"a\": 2}", "a", &json!(3)).unwrap();
+        assert_eq!(edited, "{\"a\": 1, \"a\": 3}");
+    }
+
+    #[test]
+    fn set_value_rejects_invalid_text() {
+        assert!(set_value("{\"a\": }", "a", &json!(1)).is_err());
+        assert!(set_value("[1, 2]", "a", &json!(1)).is_err());
+    }
+
+    #[test]
+    fn remove_value_deletes_whole_line() {
+        let text = "{\n  \"a\": 1,\n  // 保留的注释\n  \"b\": 2, // 删除\n  \"c\": 3\n}\n";
+        assert_eq!(
+            remove_value(text, "b").unwrap(),
+            "{\n  \"a\": 1,\n  // 保留的注释\n  \"c\": 3\n}\n"
+        );
+    }
+
+    #[test]
+    fn remove_value_fixes_trailing_comma() {
+        let text = "{\r\n  \"a\": 1,\r\n  \"b\": 2\r\n}\r\n";
+        assert_eq!(remove_value(text, "b").unwrap(), "{\r\n  \"a\": 1\r\n}\r\n");
+    }
+
+    #[test]
+    fn remove_value_on_a_single_line() {
+        assert_eq!(
+            remove_value("{\"a\": 1, \"b\": 2}", "a").unwrap(),
+            "{\"b\": 2}"
+        );
+        assert_eq!(
+            remove_value("{\"a\": 1, \"b\": 2}", "b").unwrap(),
+            "{\"a\": 1}"
+        );
+        assert_eq!(remove_value("{\"a\": 1}", "a").unwrap(), "{}");
+    }
+
+    #[test]
+    fn remove_value_removes_duplicates_and_ignores_missing() {
+        assert_eq!(
+            remove_value("{\"a\": 1, \"b\": 2, \"a\": 3}", "a").unwrap(),
+            "{\"b\": 2}"
+        );
+        let text = "{ /* 不变 */ \"a\": 1 }";
+        assert_eq!(remove_value(text, "missing").unwrap(), text);
+    }
+
+    #[test]
+    fn round_trip_preserves_formatting() {
+        let text =
+            "// 设置\n{\n\t\"model\": \"a\", /* 说明 */\n\t'nested': {\"x\": [1,\n 2]},\n}\n";
+        let edited = set_value(text, "extra", &json!("v")).unwrap();
+        let restored = remove_value(&edited, "extra").unwrap();
+        assert_eq!(restored, text);
+
+        let value = parse(&edited).unwrap();
+        assert_eq!(value["extra"], "v");
+        assert_eq!(value["nested"]["x"], json!([1, 2]));
+
+        let same = set_value(text, "model", &json!("a")).unwrap();
+        assert_eq!(same, text);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::config::{atomic_write, copy_file, read_json_file};
+use crate::error::AppError;
+use crate::models::get_builtin_models;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// 当前配置结构版本
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// 单步迁移：将原始 JSON 从版本 n 升级到 n + 1
+type Migration = fn(&mut Value) -> Result<(), AppError>;
+
+/// 按顺序执行的迁移链，MIGRATIONS[n] 负责版本 n → n + 1
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// 读取原始配置中的结构版本（没有该字段的旧配置视为 0）
+///
+/// 版本号不是非负整数或超出 u32 范围时返回错误，不按旧配置迁移。
+pub fn schema_version(raw: &Value) -> Result<u32, AppError> {
+    let Some(value) = raw.get("schemaVersion") else {
+        return Ok(0);
+    };
+    value
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| AppError::Parse(format!("配置文件的 schemaVersion 无效: {}", value)))
+}
+
+/// 对原始 JSON 执行单步迁移并更新版本号
+fn apply_step(raw: &mut Value, from: u32) -> Result<(), AppError> {
+    let migration = MIGRATIONS
+        .get(from as usize)
+        .ok_or_else(|| AppError::other(format!("缺少版本 {} 的迁移", from)))?;
+    migration(raw)?;
+
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| AppError::Parse("配置文件格式无效：根节点不是对象".to_string()))?;
+    obj.insert("schemaVersion".to_string(), Value::from(from + 1));
+    Ok(())
+}
+
+/// 拒绝加载由更新版本应用写入的配置，避免丢失未知字段
+fn ensure_supported(from: u32) -> Result<(), AppError> {
+    if from > CURRENT_SCHEMA_VERSION {
+        return Err(AppError::other(format!(
+            "配置文件版本 {} 高于当前应用支持的版本 {}，请升级应用",
+            from, CURRENT_SCHEMA_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// 迁移前备份文件路径，如 config.json.v0.bak
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "config.json".to_string());
+    path.with_file_name(format!("{}.v{}.bak", file_name, version))
+}
+
+/// 读取配置文件并迁移到当前版本
+///
+/// 每一步迁移前都会把上一版本的内容备份为 <文件名>.v<版本>.bak，
+/// 全部成功后再原子写回配置文件。
+pub fn load_and_migrate(path: &Path) -> Result<Value, AppError> {
+    let mut raw: Value = read_json_file(path)?;
+    let from = schema_version(&raw)?;
+    ensure_supported(from)?;
+    if from == CURRENT_SCHEMA_VERSION {
+        return Ok(raw);
+    }
+
+    for version in from..CURRENT_SCHEMA_VERSION {
+        // 第一步直接复制原文件，之后的步骤备份中间结果
+        if version == from {
+            copy_file(path, &backup_path(path, version))?;
+        } else {
+            let backup = serde_json::to_string_pretty(&raw)
+                .map_err(|e| AppError::parse("序列化配置备份失败", e))?;
+            atomic_write(&backup_path(path, version), backup.as_bytes())?;
+        }
+
+        apply_step(&mut raw, version)?;
+        log::info!("配置已从版本 {} 迁移到 {}", version, version + 1);
+    }
+
+    let json =
+        serde_json::to_string_pretty(&raw).map_err(|e| AppError::parse("序列化 JSON 失败", e))?;
+    atomic_write(path, json.as_bytes())?;
+    Ok(raw)
+}
+
+/// v0 → v1：引入 schemaVersion
+///
+/// - 修正 activeProviderId 与各密钥 isActive 不一致的问题
+/// - 旧版本写入的内置模型缺少 isBuiltin 标记，按内置模型 ID 补全
+fn migrate_v0_to_v1(raw: &mut Value) -> Result<(), AppError> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| AppError::Parse("配置文件格式无效：根节点不是对象".to_string()))?;
+
+    let provider_ids: Vec<String> = obj
+        .get("providers")
+        .and_then(Value::as_array)
+        .map(|providers| {
+            providers
+                .iter()
+                .filter_map(|p| p.get("id").and_then(Value::as_str).map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // 激活的密钥已不存在时清除
+    let active_id = obj
+        .get("activeProviderId")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .filter(|id| provider_ids.contains(id));
+    if active_id.is_none() {
+        obj.remove("activeProviderId");
+    }
+
+    if let Some(providers) = obj.get_mut("providers").and_then(Value::as_array_mut) {
+        for provider in providers.iter_mut().filter_map(Value::as_object_mut) {
+            let is_active = active_id.is_some()
+                && provider.get("id").and_then(Value::as_str) == active_id.as_deref();
+            provider.insert("isActive".to_string(), Value::Bool(is_active));
+        }
+    }
+
+    let builtin_ids: Vec<String> = get_builtin_models().into_iter().map(|m| m.id).collect();
+    if let Some(models) = obj
+        .get_mut("modelConfig")
+        .and_then(|c| c.get_mut("availableModels"))
+        .and_then(Value::as_array_mut)
+    {
+        for model in models.iter_mut().filter_map(Value::as_object_mut) {
+            let is_builtin = model
+                .get("id")
+                .and_then(Value::as_str)
+                .is_some_and(|id| builtin_ids.iter().any(|b| b == id));
+            if is_builtin {
+                model.insert("isBuiltin".to_string(), Value::Bool(true));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// v1 → v2：计费倍率只保存在 costMultiplier 中
+///
+/// 旧版本把倍率写在模型描述开头（如“1.2x - 日常开发默认选择”），
+/// 没有 costMultiplier 的模型从描述中解析出倍率，并去掉描述中的倍率前缀。
+fn migrate_v1_to_v2(raw: &mut Value) -> Result<(), AppError> {
+    let Some(models) = raw
+        .get_mut("modelConfig")
+        .and_then(|c| c.get_mut("availableModels"))
+        .and_then(Value::as_array_mut)
+    else {
+        return Ok(());
+    };
+
+    for model in models.iter_mut().filter_map(Value::as_object_mut) {
+        let Some((multiplier, rest)) = model
+            .get("description")
+            .and_then(Value::as_str)
+            .and_then(split_multiplier_prefix)
+        else {
+            continue;
+        };
+        if model.get("costMultiplier").is_none_or(Value::is_null) {
+            model.insert("costMultiplier".to_string(), Value::from(multiplier));
+        }
+        if rest.is_empty() {
+            model.remove("description");
+        } else {
+            model.insert("description".to_string(), Value::from(rest));
+        }
+    }
+    Ok(())
+}
+
+/// 拆分描述开头的倍率前缀，如“1.2x - 日常开发默认选择”→ (1.2, "日常开发默认选择")
+fn split_multiplier_prefix(description: &str) -> Option<(f64, String)> {
+    let (value, rest) = description.trim_start().split_once(['x', 'X'])?;
+    let multiplier: f64 = value.trim().parse().ok()?;
+    if !multiplier.is_finite() || multiplier <= 0.0 {
+        return None;
+    }
+    // 倍率之后应为结尾或分隔符，避免把“0x1 模型”之类的描述误认为倍率
+    let rest = rest.trim_start();
+    let rest = match rest.strip_prefix(['-', '–', '—', ':', '：', '·']) {
+        Some(rest) => rest.trim_start(),
+        None if rest.is_empty() => rest,
+        None => return None,
+    };
+    Some((multiplier, rest.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AppConfig;
+    use serde_json::json;
+    use std::fs;
+
+    /// 在临时目录中写入配置文件，返回文件路径
+    fn temp_config(raw: &Value) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("droid-switch-migrations-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, serde_json::to_string_pretty(raw).unwrap()).unwrap();
+        path
+    }
+
+    /// 基线版本写入的配置：没有 schemaVersion，激活状态与 activeProviderId 不一致
+    fn baseline_config() -> Value {
+        json!({
+            "providers": [
+                { "id": "a", "name": "密钥 A", "apiKey": "fk-a", "isActive": true },
+                { "id": "b", "name": "密钥 B", "apiKey": "fk-b", "isActive": false },
+            ],
+            "activeProviderId": "b",
+            "modelConfig": {
+                "availableModels": [
+                    {
+                        "id": "claude-sonnet-4-5-20250929",
+                        "name": "Claude Sonnet 4.5",
+                        "provider": "Anthropic",
+                        "description": "1.2x - 日常开发默认选择",
+                    },
+                    {
+                        "id": "my-model",
+                        "name": "自定义",
+                        "provider": "Custom",
+                        "description": "2x - 内部模型",
+                    },
+                ],
+                "selectedModelId": "claude-sonnet-4-5-20250929",
+            },
+        })
+    }
+
+    #[test]
+    fn migrates_baseline_config() {
+        let original = baseline_config();
+        let path = temp_config(&original);
+
+        let migrated = load_and_migrate(&path).unwrap();
+        assert_eq!(schema_version(&migrated).unwrap(), CURRENT_SCHEMA_VERSION);
+        assert_eq!(migrated["providers"][0]["isActive"], false);
+        assert_eq!(migrated["providers"][1]["isActive"], true);
+        assert_eq!(migrated["activeProviderId"], "b");
+        let models = &migrated["modelConfig"]["availableModels"];
+        assert_eq!(models[0]["isBuiltin"], true);
+        assert!(models[1].get("isBuiltin").is_none());
+        assert_eq!(models[0]["costMultiplier"], 1.2);
+        assert_eq!(models[0]["description"], "日常开发默认选择");
+        assert_eq!(models[1]["costMultiplier"], 2.0);
+        assert_eq!(models[1]["description"], "内部模型");
+
+        // 迁移结果写回文件，且能被当前结构读取
+        let on_disk: Value = read_json_file(&path).unwrap();
+        assert_eq!(on_disk, migrated);
+        let config: AppConfig = serde_json::from_value(migrated).unwrap();
+        assert_eq!(config.active_provider_id.as_deref(), Some("b"));
+
+        // 原文件备份为 config.json.v0.bak，中间结果备份为 config.json.v1.bak
+        let backup: Value = read_json_file(&backup_path(&path, 0)).unwrap();
+        assert_eq!(backup, original);
+        let backup: Value = read_json_file(&backup_path(&path, 1)).unwrap();
+        assert_eq!(schema_version(&backup).unwrap(), 1);
+        assert_eq!(
+            backup["modelConfig"]["availableModels"][1]["description"],
+            "2x - 内部模型"
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn clears_missing_active_provider() {
+        let mut raw = json!({
+            "providers": [{ "id": "a", "name": "A", "apiKey": "fk-a", "isActive": true }],
+            "activeProviderId": "gone",
+        });
+        migrate_v0_to_v1(&mut raw).unwrap();
+        assert!(raw.get("activeProviderId").is_none());
+        assert_eq!(raw["providers"][0]["isActive"], false);
+    }
+
+    #[test]
+    fn moves_description_multiplier_into_cost_multiplier() {
+        let mut raw = json!({
+            "modelConfig": {
+                "availableModels": [
+                    { "id": "a", "description": "0.5x" },
+                    { "id": "b", "description": "3x - 已有倍率", "costMultiplier": 4.0 },
+                    { "id": "c", "description": "0x1 不是倍率" },
+                    { "id": "d", "description": "普通描述" },
+                    { "id": "e" },
+                ],
+            },
+        });
+        migrate_v1_to_v2(&mut raw).unwrap();
+        let models = &raw["modelConfig"]["availableModels"];
+        assert_eq!(models[0]["costMultiplier"], 0.5);
+        assert!(models[0].get("description").is_none());
+        assert_eq!(models[1]["costMultiplier"], 4.0);
+        assert_eq!(models[1]["description"], "已有倍率");
+        assert!(models[2].get("costMultiplier").is_none());
+        assert_eq!(models[2]["description"], "0x1 不是倍率");
+        assert_eq!(models[3]["description"], "普通描述");
+        assert!(models[4].get("costMultiplier").is_none());
+    }
+
+    #[test]
+    fn current_config_is_left_untouched() {
+        let raw = json!({ "schemaVersion": CURRENT_SCHEMA_VERSION, "providers": [] });
+        let path = temp_config(&raw);
+
+        assert_eq!(load_and_migrate(&path).unwrap(), raw);
+        assert!(!backup_path(&path, 0).exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_newer_schema_version() {
+        let raw = json!({ "schemaVersion": CURRENT_SCHEMA_VERSION + 1, "providers": [] });
+        let path = temp_config(&raw);
+        let before = fs::read_to_string(&path).unwrap();
+
+        assert!(load_and_migrate(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_schema_version() {
+        assert_eq!(schema_version(&json!({})).unwrap(), 0);
+        assert_eq!(schema_version(&json!({ "schemaVersion": 2 })).unwrap(), 2);
+        for version in [
+            json!(u64::from(u32::MAX) + 1),
+            json!(-1),
+            json!(1.5),
+            json!("2"),
+        ] {
+            assert!(schema_version(&json!({ "schemaVersion": version })).is_err());
+        }
+
+        let raw = json!({ "schemaVersion": u64::from(u32::MAX) + 1, "providers": [] });
+        let path = temp_config(&raw);
+        let before = fs::read_to_string(&path).unwrap();
+
+        assert!(load_and_migrate(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert!(!backup_path(&path, 0).exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_non_object_root() {
+        assert!(apply_step(&mut json!([1, 2]), 0).is_err());
+    }
+}
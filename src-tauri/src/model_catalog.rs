@@ -0,0 +1,248 @@
+use crate::config::{get_app_config_dir, read_json_file, run_blocking, write_json_file};
+use crate::error::AppError;
+use crate::models::{
+    get_builtin_models, ManifestModel, ModelCatalogCache, ModelCatalogSettings, ModelCatalogStatus,
+    ModelInfo, ModelManifest,
+};
+use crate::network;
+use crate::provider_manager::{load_config, update_config};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// 支持的模型清单结构版本
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// 获取模型清单缓存文件路径
+pub fn get_model_catalog_cache_path() -> PathBuf {
+    get_app_config_dir().join("model_catalog.json")
+}
+
+/// 获取外部模型清单设置
+pub fn get_settings() -> Result<ModelCatalogSettings, AppError> {
+    Ok(load_config()?.model_catalog)
+}
+
+/// 保存外部模型清单设置（不会立即获取清单，需调用 `refresh_catalog`）
+pub fn set_settings(mut settings: ModelCatalogSettings) -> Result<(), AppError> {
+    if settings.refresh_hours == 0 {
+        return Err(AppError::invalid_input("清单刷新间隔必须大于 0 小时"));
+    }
+    settings.source = settings
+        .source
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    update_config(|config| {
+        config.model_catalog = settings;
+        Ok(())
+    })?;
+    log::info!("已更新模型清单设置");
+    Ok(())
+}
+
+/// 解析并校验模型清单
+pub fn parse_manifest(content: &str) -> Result<ModelManifest, AppError> {
+    let manifest: ModelManifest =
+        serde_json::from_str(content).map_err(|e| AppError::parse("解析模型清单失败", e))?;
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+fn validate_manifest(manifest: &ModelManifest) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Parse(format!("模型清单无效: {}", message));
+
+    if manifest.version != MANIFEST_VERSION {
+        return Err(invalid(format!(
+            "不支持的版本 {}（支持版本 {}）",
+            manifest.version, MANIFEST_VERSION
+        )));
+    }
+    if manifest.models.is_empty() {
+        return Err(invalid("没有模型".to_string()));
+    }
+
+    let mut ids = HashSet::new();
+    for model in &manifest.models {
+        if model.id.trim().is_empty() {
+            return Err(invalid("模型 ID 为空".to_string()));
+        }
+        if !ids.insert(model.id.as_str()) {
+            return Err(invalid(format!("模型 ID {} 重复", model.id)));
+        }
+        if model.name.trim().is_empty() || model.provider.trim().is_empty() {
+            return Err(invalid(format!("模型 {} 缺少名称或提供商", model.id)));
+        }
+        if !model.multiplier.is_finite() || model.multiplier <= 0.0 {
+            return Err(invalid(format!("模型 {} 的倍率必须大于 0", model.id)));
+        }
+        if !model.reasoning_levels.is_empty()
+            && !model.reasoning_levels.contains(&model.default_reasoning)
+        {
+            return Err(invalid(format!(
+                "模型 {} 的默认推理级别不在允许的级别中",
+                model.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 清单中的模型转换为内置模型
+fn to_model_info(model: ManifestModel) -> ModelInfo {
+    ModelInfo {
+        id: model.id,
+        name: model.name,
+        provider: model.provider,
+        description: model.description,
+        is_builtin: true,
+        reasoning_level: model.default_reasoning,
+        cost_multiplier: Some(model.multiplier),
+        reasoning_levels: model.reasoning_levels,
+        deprecated: false,
+    }
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// 从本地文件或 HTTP(S) 地址读取模型清单（URL 按网络设置使用代理和证书）
+pub async fn fetch_manifest(source: &str) -> Result<ModelManifest, AppError> {
+    let content = if is_url(source) {
+        let settings = run_blocking(|| Ok(load_config()?.network)).await?;
+        let client = network::shared_client(&settings)?;
+        let request_error = |e: reqwest::Error| {
+            let message = format!("获取模型清单 {} 失败: {}", source, e);
+            if e.is_timeout() {
+                AppError::Timeout(message)
+            } else {
+                AppError::Network(message)
+            }
+        };
+
+        let response = client.get(source).send().await.map_err(request_error)?;
+        let status = response.status();
+        let body = response.text().await.map_err(request_error)?;
+        if !status.is_success() {
+            return Err(AppError::Http {
+                status: status.as_u16(),
+                message: body,
+            });
+        }
+        body
+    } else {
+        let path = source.to_string();
+        run_blocking(move || {
+            fs::read_to_string(&path)
+                .map_err(|e| AppError::io(&format!("读取模型清单 {} 失败", path), e))
+        })
+        .await?
+    };
+    parse_manifest(&content)
+}
+
+/// 读取与当前来源一致且校验通过的清单缓存
+fn cached_manifest(source: &str) -> Option<ModelCatalogCache> {
+    let path = get_model_catalog_cache_path();
+    if !path.exists() {
+        return None;
+    }
+
+    match read_json_file::<ModelCatalogCache>(&path) {
+        Ok(cache) if cache.source != source => None,
+        Ok(cache) => match validate_manifest(&cache.manifest) {
+            Ok(()) => Some(cache),
+            Err(e) => {
+                log::warn!("模型清单缓存无效，使用内置模型列表: {}", e);
+                None
+            }
+        },
+        Err(e) => {
+            log::warn!("读取模型清单缓存失败，使用内置模型列表: {}", e);
+            None
+        }
+    }
+}
+
+/// 当前的内置模型目录：已配置且成功获取过的清单，否则为编译时的内置模型列表
+pub fn catalog_models(settings: &ModelCatalogSettings) -> Vec<ModelInfo> {
+    settings
+        .source
+        .as_deref()
+        .and_then(cached_manifest)
+        .map(|cache| {
+            cache
+                .manifest
+                .models
+                .into_iter()
+                .map(to_model_info)
+                .collect()
+        })
+        .unwrap_or_else(get_builtin_models)
+}
+
+/// 获取当前使用的模型目录
+pub fn get_status() -> Result<ModelCatalogStatus, AppError> {
+    let settings = get_settings()?;
+    let status = match settings.source.as_deref().and_then(cached_manifest) {
+        Some(cache) => ModelCatalogStatus {
+            source: settings.source,
+            version: Some(cache.manifest.version),
+            model_count: cache.manifest.models.len(),
+            fetched_at: Some(cache.fetched_at),
+            fallback: false,
+        },
+        None => ModelCatalogStatus {
+            source: settings.source,
+            version: None,
+            model_count: get_builtin_models().len(),
+            fetched_at: None,
+            fallback: true,
+        },
+    };
+    Ok(status)
+}
+
+/// 已配置清单且缓存不存在或超过刷新间隔时需要重新获取
+pub fn needs_refresh() -> Result<bool, AppError> {
+    let settings = get_settings()?;
+    let Some(source) = settings.source.as_deref() else {
+        return Ok(false);
+    };
+    let max_age = chrono::Duration::hours(settings.refresh_hours as i64);
+    Ok(cached_manifest(source)
+        .and_then(|cache| DateTime::parse_from_rfc3339(&cache.fetched_at).ok())
+        .is_none_or(|t| Utc::now() - t.with_timezone(&Utc) >= max_age))
+}
+
+/// 重新获取模型清单并更新缓存和模型列表
+///
+/// 获取或校验失败时保留原有缓存；从未成功获取过时继续使用内置模型列表。
+pub async fn refresh_catalog() -> Result<ModelCatalogStatus, AppError> {
+    let Some(source) = run_blocking(get_settings).await?.source else {
+        return run_blocking(get_status).await;
+    };
+
+    let manifest = fetch_manifest(&source).await.inspect_err(|e| {
+        log::warn!("获取模型清单失败，继续使用当前模型目录: {}", e);
+    })?;
+    let count = manifest.models.len();
+    run_blocking(move || {
+        write_json_file(
+            &get_model_catalog_cache_path(),
+            &ModelCatalogCache {
+                source: source.clone(),
+                fetched_at: Utc::now().to_rfc3339(),
+                manifest,
+            },
+        )?;
+
+        // 加载配置时会合并新的模型目录，保存一次写入配置文件
+        update_config(|_| Ok(()))?;
+        log::info!("已从 {} 获取模型清单，共 {} 个模型", source, count);
+        get_status()
+    })
+    .await
+}
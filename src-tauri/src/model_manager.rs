@@ -1,50 +1,165 @@
-use crate::models::{get_builtin_models, ModelInfo, ReasoningLevel};
-use crate::provider_manager::{load_config, save_config};
+use crate::balance::format_tokens;
+use crate::config::{atomic_write, lock_app_config};
+use crate::error::AppError;
+use crate::jsonc;
+use crate::model_catalog;
+use crate::models::{
+    AppConfig, BalanceInfo, ModelInfo, ModelQuota, ProjectSession, ReasoningLevel, DEFAULT_MODEL_ID,
+};
+use crate::provider_manager::{load_config, update_config};
+use chrono::Utc;
 use log::info;
 use std::fs;
 use std::path::PathBuf;
 
 /// 获取 Factory settings 配置文件路径
-fn get_factory_settings_path() -> Result<PathBuf, String> {
-    let home_dir = dirs::home_dir().ok_or("无法获取用户主目录")?;
+fn get_factory_settings_path() -> Result<PathBuf, AppError> {
+    let home_dir = dirs::home_dir().ok_or_else(|| AppError::other("无法获取用户主目录"))?;
     Ok(home_dir.join(".factory").join("settings.json"))
 }
 
 /// 获取所有可用模型
-pub fn get_available_models() -> Result<Vec<ModelInfo>, String> {
-    let mut config = load_config()?;
+pub fn get_available_models() -> Result<Vec<ModelInfo>, AppError> {
+    Ok(load_config()?.model_config.available_models)
+}
 
-    // 如果配置为空,初始化为内置模型
-    if config.model_config.available_models.is_empty() {
-        config.model_config.available_models = get_builtin_models();
-        save_config(&config)?;
+/// 将内置模型目录（内置列表或外部模型清单）合并到已保存的模型列表（加载配置时调用）
+///
+/// 新增的内置模型追加到列表末尾；已有内置模型更新名称、提供商、描述、倍率和允许的推理级别，
+/// 保留用户设置的推理级别（不再允许时改用默认级别）；目录中已移除的内置模型标记为已弃用。
+/// 自定义模型不做修改。
+pub fn merge_builtin_models(models: &mut Vec<ModelInfo>, builtins: Vec<ModelInfo>) {
+    let (mut added, mut updated, mut deprecated) = (0, 0, 0);
+
+    for model in models.iter_mut().filter(|m| m.is_builtin) {
+        let removed = !builtins.iter().any(|b| b.id == model.id);
+        if model.deprecated != removed {
+            model.deprecated = removed;
+            deprecated += usize::from(removed);
+        }
     }
 
-    Ok(config.model_config.available_models.clone())
+    for builtin in builtins {
+        match models.iter_mut().find(|m| m.id == builtin.id) {
+            Some(model) if model.is_builtin => {
+                if model.name != builtin.name
+                    || model.provider != builtin.provider
+                    || model.description != builtin.description
+                    || model.cost_multiplier != builtin.cost_multiplier
+                    || model.reasoning_levels != builtin.reasoning_levels
+                {
+                    model.name = builtin.name;
+                    model.provider = builtin.provider;
+                    model.description = builtin.description;
+                    model.cost_multiplier = builtin.cost_multiplier;
+                    model.reasoning_levels = builtin.reasoning_levels;
+                    updated += 1;
+                }
+                if !is_reasoning_allowed(model, &model.reasoning_level) {
+                    model.reasoning_level = builtin.reasoning_level;
+                }
+            }
+            // 同 ID 的自定义模型优先
+            Some(_) => {}
+            None => {
+                models.push(builtin);
+                added += 1;
+            }
+        }
+    }
+
+    if added + updated + deprecated > 0 {
+        log::debug!(
+            "已同步内置模型: 新增 {}，更新 {}，弃用 {}",
+            added,
+            updated,
+            deprecated
+        );
+    }
 }
 
 /// 获取当前选中的模型
-pub fn get_selected_model() -> Result<Option<String>, String> {
+pub fn get_selected_model() -> Result<Option<String>, AppError> {
     let config = load_config()?;
     Ok(config.model_config.selected_model_id.clone())
 }
 
-/// 设置选中的模型
-pub fn set_selected_model(model_id: String) -> Result<(), String> {
-    let mut config = load_config()?;
+/// 模型的计费倍率（未设置或无效时为空）
+pub fn cost_multiplier(model: &ModelInfo) -> Option<f64> {
+    model.cost_multiplier.filter(|m| m.is_finite() && *m > 0.0)
+}
+
+/// 按模型倍率把剩余额度折算为该模型实际可用的 tokens（倍率未知时为空）
+pub fn effective_remaining_tokens(balance: &BalanceInfo, model: &ModelInfo) -> Option<u64> {
+    let multiplier = cost_multiplier(model)?;
+    Some((balance.remaining as f64 / multiplier).floor() as u64)
+}
 
-    // 查找模型并验证是否存在
+/// 当前选中模型下剩余额度的提示（如“≈ 16.3M Claude Sonnet 4.5 tokens”），倍率为 1 或未知时为空
+pub fn effective_remaining_hint(config: &AppConfig, balance: &BalanceInfo) -> Option<String> {
+    let selected = config.model_config.selected_model_id.as_deref()?;
     let model = config
         .model_config
         .available_models
         .iter()
-        .find(|m| m.id == model_id)
-        .cloned();
+        .find(|m| m.id == selected)?;
+    if cost_multiplier(model)? == 1.0 {
+        return None;
+    }
+    let tokens = effective_remaining_tokens(balance, model)?;
+    Some(format!("≈ {} {} tokens", format_tokens(tokens), model.name))
+}
 
-    let model = model.ok_or(format!("模型 '{}' 不存在", model_id))?;
+/// 密钥剩余额度在各模型下实际可用的 tokens，按可用量从多到少排列（跳过已弃用和倍率未知的模型）
+pub fn get_model_quotas(provider_id: &str) -> Result<Vec<ModelQuota>, AppError> {
+    let config = load_config()?;
+    let provider = config
+        .providers
+        .iter()
+        .find(|p| p.id == provider_id)
+        .ok_or_else(|| AppError::not_found("密钥", provider_id))?;
+    let Some(balance) = &provider.balance else {
+        return Ok(Vec::new());
+    };
 
-    config.model_config.selected_model_id = Some(model_id.clone());
-    save_config(&config)?;
+    let mut quotas: Vec<ModelQuota> = config
+        .model_config
+        .available_models
+        .iter()
+        .filter(|m| !m.deprecated)
+        .filter_map(|m| {
+            Some(ModelQuota {
+                model_id: m.id.clone(),
+                model_name: m.name.clone(),
+                cost_multiplier: cost_multiplier(m)?,
+                effective_remaining: effective_remaining_tokens(balance, m)?,
+            })
+        })
+        .collect();
+    quotas.sort_by_key(|q| std::cmp::Reverse(q.effective_remaining));
+    Ok(quotas)
+}
+
+/// 设置选中的模型
+///
+/// 配置和 Factory settings.json 在同一次持有配置锁期间写入，避免与其他进程的模型切换交错。
+/// 项目会话不修改 settings.json，会话期间切换全局模型不会被会话结束时覆盖。
+pub fn set_selected_model(model_id: String) -> Result<(), AppError> {
+    let _lock = lock_app_config()?;
+    let model = update_config(|config| {
+        // 查找模型并验证是否存在
+        let model = config
+            .model_config
+            .available_models
+            .iter()
+            .find(|m| m.id == model_id)
+            .cloned();
+
+        let model = model.ok_or_else(|| AppError::not_found("模型", model_id.clone()))?;
+
+        config.model_config.selected_model_id = Some(model_id.clone());
+        Ok(model)
+    })?;
 
     // 更新 Factory settings.json 配置文件
     update_factory_settings(&model_id, &model.reasoning_level)?;
@@ -60,93 +175,230 @@ pub fn add_custom_model(
     provider: String,
     description: Option<String>,
     reasoning_level: Option<ReasoningLevel>,
-) -> Result<(), String> {
-    let mut config = load_config()?;
-
-    // 检查模型 ID 是否已存在
-    if config
-        .model_config
-        .available_models
-        .iter()
-        .any(|m| m.id == id)
-    {
-        return Err(format!("模型 ID '{}' 已存在", id));
+    cost_multiplier: Option<f64>,
+) -> Result<(), AppError> {
+    if cost_multiplier.is_some_and(|m| !m.is_finite() || m <= 0.0) {
+        return Err(AppError::invalid_input("计费倍率必须大于 0"));
     }
 
-    let new_model = ModelInfo {
-        id,
-        name,
-        provider,
-        description,
-        is_builtin: false,
-        reasoning_level: reasoning_level.unwrap_or_default(),
-    };
+    update_config(|config| {
+        // 检查模型 ID 是否已存在
+        if config
+            .model_config
+            .available_models
+            .iter()
+            .any(|m| m.id == id)
+        {
+            return Err(AppError::duplicate(
+                "id",
+                format!("模型 ID '{}' 已存在", id),
+            ));
+        }
 
-    config.model_config.available_models.push(new_model);
-    save_config(&config)?;
+        let new_model = ModelInfo {
+            id,
+            name,
+            provider,
+            description,
+            is_builtin: false,
+            reasoning_level: reasoning_level.unwrap_or_default(),
+            cost_multiplier,
+            reasoning_levels: Vec::new(),
+            deprecated: false,
+        };
+
+        config.model_config.available_models.push(new_model);
+        Ok(())
+    })?;
 
     info!("自定义模型添加成功");
     Ok(())
 }
 
-/// 删除自定义模型
-pub fn remove_custom_model(model_id: String) -> Result<(), String> {
-    let mut config = load_config()?;
-
-    // 查找模型
-    let model = config
-        .model_config
-        .available_models
-        .iter()
-        .find(|m| m.id == model_id)
-        .ok_or(format!("模型 '{}' 不存在", model_id))?;
+/// 删除自定义模型或已弃用的内置模型
+///
+/// 删除的是当前选中的模型时改选默认模型，并在配置锁内同步 Factory settings.json。
+pub fn remove_custom_model(model_id: String) -> Result<(), AppError> {
+    let _lock = lock_app_config()?;
+    let reselected = update_config(|config| {
+        // 查找模型
+        let model = config
+            .model_config
+            .available_models
+            .iter()
+            .find(|m| m.id == model_id)
+            .ok_or_else(|| AppError::not_found("模型", model_id.clone()))?;
+
+        // 不允许删除仍在内置列表中的模型
+        if model.is_builtin && !model.deprecated {
+            return Err(AppError::invalid_input("不能删除内置模型"));
+        }
 
-    // 不允许删除内置模型
-    if model.is_builtin {
-        return Err("不能删除内置模型".to_string());
-    }
+        config
+            .model_config
+            .available_models
+            .retain(|m| m.id != model_id);
 
-    // 如果删除的是当前选中的模型,重置为默认模型
-    if config.model_config.selected_model_id.as_ref() == Some(&model_id) {
-        config.model_config.selected_model_id = Some("claude-sonnet-4-5-20250929".to_string());
+        // 如果删除的是当前选中的模型,重置为默认模型
+        if config.model_config.selected_model_id.as_ref() != Some(&model_id) {
+            return Ok(None);
+        }
+        let default = default_model(&config.model_config.available_models);
+        config.model_config.selected_model_id = default.as_ref().map(|m| m.id.clone());
+        Ok(default)
+    })?;
+
+    if let Some(model) = reselected {
+        update_factory_settings(&model.id, &model.reasoning_level)?;
+        info!("已改选默认模型: {}", model.id);
     }
 
-    config
-        .model_config
-        .available_models
-        .retain(|m| m.id != model_id);
-    save_config(&config)?;
-
     info!("自定义模型已删除: {}", model_id);
     Ok(())
 }
 
-/// 设置模型的推理级别
-pub fn set_model_reasoning_level(model_id: String, reasoning_level: ReasoningLevel) -> Result<(), String> {
-    let mut config = load_config()?;
+/// 默认模型：目录中的默认模型，已移除或弃用时使用第一个可用的内置模型
+fn default_model(models: &[ModelInfo]) -> Option<ModelInfo> {
+    let usable = || models.iter().filter(|m| m.is_builtin && !m.deprecated);
+    usable()
+        .find(|m| m.id == DEFAULT_MODEL_ID)
+        .or_else(|| usable().next())
+        .cloned()
+}
 
-    // 查找模型
-    let model = config
-        .model_config
-        .available_models
-        .iter_mut()
-        .find(|m| m.id == model_id)
-        .ok_or(format!("模型 '{}' 不存在", model_id))?;
+/// 模型是否允许使用该推理级别
+fn is_reasoning_allowed(model: &ModelInfo, level: &ReasoningLevel) -> bool {
+    model.reasoning_levels.is_empty() || model.reasoning_levels.contains(level)
+}
 
-    model.reasoning_level = reasoning_level.clone();
-    save_config(&config)?;
+/// 设置模型的推理级别（与 set_selected_model 一样在配置锁内写入 settings.json）
+pub fn set_model_reasoning_level(
+    model_id: String,
+    reasoning_level: ReasoningLevel,
+) -> Result<(), AppError> {
+    let _lock = lock_app_config()?;
+    let is_selected = update_config(|config| {
+        // 查找模型
+        let model = config
+            .model_config
+            .available_models
+            .iter_mut()
+            .find(|m| m.id == model_id)
+            .ok_or_else(|| AppError::not_found("模型", model_id.clone()))?;
+
+        if !is_reasoning_allowed(model, &reasoning_level) {
+            return Err(AppError::invalid_input(format!(
+                "模型 {} 不支持推理级别 {:?}",
+                model_id, reasoning_level
+            )));
+        }
+
+        model.reasoning_level = reasoning_level.clone();
+        Ok(config.model_config.selected_model_id.as_ref() == Some(&model_id))
+    })?;
 
     // 如果这是当前选中的模型，更新 Factory settings.json
-    if config.model_config.selected_model_id.as_ref() == Some(&model_id) {
+    if is_selected {
         update_factory_settings(&model_id, &reasoning_level)?;
     }
 
-    info!("模型 {} 的推理级别已设置为: {:?}", model_id, reasoning_level);
+    info!(
+        "模型 {} 的推理级别已设置为: {:?}",
+        model_id, reasoning_level
+    );
     Ok(())
 }
 
+/// 模型配置的推理级别（模型不存在时使用默认级别）
+fn configured_reasoning_level(config: &AppConfig, model_id: &str) -> ReasoningLevel {
+    config
+        .model_config
+        .available_models
+        .iter()
+        .find(|m| m.id == model_id)
+        .map(|m| m.reasoning_level.clone())
+        .unwrap_or_default()
+}
+
+/// 开始项目会话：登记会话并返回项目生效的模型和推理级别（不修改全局选择和 Factory settings.json）
+///
+/// 项目设置由包装函数通过环境变量传给本次运行的 droid，项目外运行的 droid 仍使用全局设置。
+/// 只指定模型时使用该模型配置的推理级别；都未指定时沿用全局设置。
+/// 同时清理 shell 进程已退出（被强制结束、终端关闭等）而未结束的会话。
+pub fn begin_project_session(
+    session_id: &str,
+    project_dir: &str,
+    pid: Option<u32>,
+    model_id: Option<&str>,
+    reasoning_level: Option<ReasoningLevel>,
+) -> Result<ProjectSession, AppError> {
+    update_config(|config| {
+        let reasoning_level =
+            reasoning_level.or_else(|| model_id.map(|id| configured_reasoning_level(config, id)));
+        let session = ProjectSession {
+            id: session_id.to_string(),
+            project_dir: project_dir.to_string(),
+            pid,
+            model_id: model_id.map(str::to_string),
+            reasoning_level,
+            started_at: Utc::now().to_rfc3339(),
+        };
+        prune_stale_sessions(config);
+        config.project_sessions.retain(|s| s.id != session_id);
+        config.project_sessions.push(session.clone());
+        Ok(session)
+    })
+}
+
+/// 结束项目会话
+///
+/// session_id 为空时结束所有项目会话（用于 droid 异常退出后手动清理）。
+/// 会话不存在（已被结束）时不做修改。同时清理 shell 进程已退出的会话。
+pub fn end_project_session(session_id: Option<&str>) -> Result<(), AppError> {
+    update_config(|config| {
+        prune_stale_sessions(config);
+        match session_id {
+            Some(id) => config.project_sessions.retain(|s| s.id != id),
+            None => config.project_sessions.clear(),
+        }
+        Ok(())
+    })
+}
+
+/// 移除 shell 进程已退出的项目会话（未记录进程 ID 的旧会话无法确认，一并移除）
+fn prune_stale_sessions(config: &mut AppConfig) {
+    config.project_sessions.retain(|s| {
+        let alive = s.pid.is_some_and(is_process_alive);
+        if !alive {
+            log::info!("清理已退出的项目会话: {} ({})", s.id, s.project_dir);
+        }
+        alive
+    });
+}
+
+/// 进程是否仍在运行
+#[cfg(not(target_os = "windows"))]
+fn is_process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// 进程是否仍在运行
+#[cfg(target_os = "windows")]
+fn is_process_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+        })
+}
+
 /// 将 ReasoningLevel 转换为 Factory settings 中的 reasoningEffort 值
-fn reasoning_level_to_effort(level: &ReasoningLevel) -> &'static str {
+pub fn reasoning_level_to_effort(level: &ReasoningLevel) -> &'static str {
     match level {
         ReasoningLevel::Off => "off",
         ReasoningLevel::Low => "low",
@@ -156,117 +408,63 @@ fn reasoning_level_to_effort(level: &ReasoningLevel) -> &'static str {
 }
 
 /// 更新 Factory settings.json 配置文件中的模型和推理级别
-fn update_factory_settings(model_id: &str, reasoning_level: &ReasoningLevel) -> Result<(), String> {
+///
+/// 只替换 model 和 reasoningEffort 的值，保留用户的注释、键顺序和尾随逗号。
+/// 文件无法解析时返回错误，不修改文件。
+fn update_factory_settings(
+    model_id: &str,
+    reasoning_level: &ReasoningLevel,
+) -> Result<(), AppError> {
     let settings_path = get_factory_settings_path()?;
-
-    // 如果 Factory settings 文件不存在,创建一个基础配置
-    if !settings_path.exists() {
-        let factory_dir = settings_path
-            .parent()
-            .ok_or("无法获取 Factory 配置目录")?;
-
-        fs::create_dir_all(factory_dir)
-            .map_err(|e| format!("创建 Factory 配置目录失败: {}", e))?;
-
+    let effort = reasoning_level_to_effort(reasoning_level);
+
+    let content = if settings_path.exists() {
+        let content = fs::read_to_string(&settings_path)
+            .map_err(|e| AppError::io("读取 Factory 配置失败", e))?;
+        jsonc::set_value(&content, "model", &serde_json::json!(model_id))
+            .and_then(|c| jsonc::set_value(&c, "reasoningEffort", &serde_json::json!(effort)))
+            .map_err(|e| AppError::parse("解析 Factory 配置失败，未修改文件", e))?
+    } else {
+        // 如果 Factory settings 文件不存在,创建一个基础配置
         let default_config = serde_json::json!({
             "model": model_id,
-            "reasoningEffort": reasoning_level_to_effort(reasoning_level)
+            "reasoningEffort": effort
         });
+        serde_json::to_string_pretty(&default_config)
+            .map_err(|e| AppError::parse("序列化配置失败", e))?
+    };
 
-        let content = serde_json::to_string_pretty(&default_config)
-            .map_err(|e| format!("序列化配置失败: {}", e))?;
-
-        fs::write(&settings_path, content)
-            .map_err(|e| format!("写入 Factory 配置失败: {}", e))?;
-
-        info!("已创建 Factory settings 文件: {:?}", settings_path);
-        return Ok(());
-    }
-
-    // 读取现有配置 (settings.json 可能包含注释，需要使用 json5 或者手动处理)
-    let config_content = fs::read_to_string(&settings_path)
-        .map_err(|e| format!("读取 Factory 配置失败: {}", e))?;
-
-    // 尝试解析 JSON (忽略注释行)
-    let clean_json = remove_json_comments(&config_content);
-    let mut config: serde_json::Value = serde_json::from_str(&clean_json)
-        .map_err(|e| format!("解析 Factory 配置失败: {}", e))?;
-
-    // 更新 model 和 reasoningEffort
-    if let Some(obj) = config.as_object_mut() {
-        obj.insert("model".to_string(), serde_json::json!(model_id));
-        obj.insert("reasoningEffort".to_string(), serde_json::json!(reasoning_level_to_effort(reasoning_level)));
-    }
-
-    // 写回文件
-    let updated_content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("序列化配置失败: {}", e))?;
-
-    fs::write(&settings_path, updated_content)
-        .map_err(|e| format!("写入 Factory 配置失败: {}", e))?;
+    atomic_write(&settings_path, content.as_bytes())?;
 
-    info!("已更新 Factory settings: model={}, reasoningEffort={}", model_id, reasoning_level_to_effort(reasoning_level));
+    info!(
+        "已更新 Factory settings: model={}, reasoningEffort={}",
+        model_id, effort
+    );
     Ok(())
 }
 
-/// 移除 JSON 中的注释 (支持 // 和 /* */ 风格)
-fn remove_json_comments(content: &str) -> String {
-    let mut result = String::new();
-    let mut in_string = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-    let mut chars = content.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if in_line_comment {
-            if c == '\n' {
-                in_line_comment = false;
-                result.push(c);
-            }
-            continue;
-        }
-
-        if in_block_comment {
-            if c == '*' && chars.peek() == Some(&'/') {
-                chars.next();
-                in_block_comment = false;
-            }
-            continue;
-        }
-
-        if c == '"' && !in_block_comment && !in_line_comment {
-            in_string = !in_string;
-            result.push(c);
-            continue;
-        }
+/// 重置模型配置为默认值
+///
+/// 选中的模型或其推理级别因此改变时，在配置锁内同步 Factory settings.json。
+pub fn reset_models_config() -> Result<(), AppError> {
+    let _lock = lock_app_config()?;
+    let changed = update_config(|config| {
+        let previous = config.model_config.selected_model_id.clone().map(|id| {
+            let level = configured_reasoning_level(config, &id);
+            (id, level)
+        });
+        config.model_config.available_models = model_catalog::catalog_models(&config.model_catalog);
+        let default = default_model(&config.model_config.available_models);
+        config.model_config.selected_model_id = default.as_ref().map(|m| m.id.clone());
 
-        if !in_string {
-            if c == '/' {
-                if chars.peek() == Some(&'/') {
-                    chars.next();
-                    in_line_comment = true;
-                    continue;
-                } else if chars.peek() == Some(&'*') {
-                    chars.next();
-                    in_block_comment = true;
-                    continue;
-                }
-            }
-        }
+        let current = default.map(|m| (m.id, m.reasoning_level));
+        Ok(current.filter(|c| previous.as_ref() != Some(c)))
+    })?;
 
-        result.push(c);
+    if let Some((model_id, reasoning_level)) = changed {
+        update_factory_settings(&model_id, &reasoning_level)?;
     }
 
-    result
-}
-
-/// 重置模型配置为默认值
-pub fn reset_models_config() -> Result<(), String> {
-    let mut config = load_config()?;
-    config.model_config.available_models = get_builtin_models();
-    config.model_config.selected_model_id = Some("claude-sonnet-4-5-20250929".to_string());
-    save_config(&config)?;
-
     info!("模型配置已重置为默认值");
     Ok(())
 }
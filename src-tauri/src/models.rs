@@ -1,4 +1,6 @@
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// API Key 密钥
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,15 +8,128 @@ use serde::{Deserialize, Serialize};
 pub struct Provider {
     pub id: String,
     pub name: String,
+    /// 备注
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// 明文 API Key；启用密钥库后磁盘上为空，解锁时由 encrypted_api_key 解密填充。
+    /// 保存在外部存储（如系统钥匙串）时为空，需通过 secret_store 解析
+    #[serde(default)]
     pub api_key: String,
+    /// 密钥库加密后的 API Key（enc:v1:<nonce>:<ciphertext>）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_api_key: Option<String>,
+    /// 外部存储中的 API Key 引用（为空表示保存在本配置中）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<SecretRef>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub balance: Option<BalanceInfo>,
+    /// 最近一次成功查询余额的时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_checked_at: Option<String>,
+    /// 余额是否已过时（超过设置的时间未成功查询，加载配置时计算，不写入配置文件）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub balance_stale: bool,
+    /// 最近一次查询失败的原因（查询成功后清除）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_at: Option<String>,
+    /// 连续查询失败次数
+    #[serde(default)]
+    pub consecutive_failures: u32,
     #[serde(default)]
     pub is_active: bool,
+    /// 是否已过期（根据余额中的到期时间自动标记）
+    #[serde(default)]
+    pub expired: bool,
+    /// 密钥状态（根据每次余额查询的结果更新）
+    #[serde(default)]
+    pub status: ProviderStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<String>,
+    /// 该密钥的额度提醒阈值（已用百分比，为空使用全局设置）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_thresholds: Option<Vec<f64>>,
+    /// 额度提醒状态
+    #[serde(default, skip_serializing_if = "QuotaAlertState::is_empty")]
+    pub alert_state: QuotaAlertState,
+}
+
+/// 密钥状态
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ProviderStatus {
+    /// 尚未查询过
+    #[default]
+    Unknown,
+    /// 查询成功且额度可用
+    Valid,
+    /// API Key 不被接受（401）
+    Invalid,
+    /// API Key 已被吊销或禁用（403）
+    Revoked,
+    /// 已过到期时间
+    Expired,
+    /// 配额已用尽
+    Exhausted,
+}
+
+/// 单个密钥的校验结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyValidation {
+    pub provider_id: String,
+    pub provider_name: String,
+    pub status: ProviderStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+/// 密钥的额度提醒状态（记录已提醒过的阈值，避免每次刷新重复提醒）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaAlertState {
+    /// 已提醒过的阈值
+    #[serde(default)]
+    pub fired_thresholds: Vec<f64>,
+    /// 是否已提醒过超额
+    #[serde(default)]
+    pub exceeded_notified: bool,
+    /// 已提醒过的到期前天数
+    #[serde(default)]
+    pub expiry_warned_days: Vec<u32>,
+}
+
+impl QuotaAlertState {
+    pub fn is_empty(&self) -> bool {
+        self.fired_thresholds.is_empty()
+            && !self.exceeded_notified
+            && self.expiry_warned_days.is_empty()
+    }
+}
+
+/// API Key 存储后端
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SecretBackend {
+    /// 保存在应用 config.json 中（可配合密钥库加密）
+    #[default]
+    Inline,
+    /// 系统钥匙串（Linux Secret Service / macOS Keychain / Windows 凭据管理器）
+    Keyring,
+    /// 进程内存（仅用于测试，不持久化）
+    #[cfg(test)]
+    Memory,
+}
+
+/// 外部存储中的 API Key 引用
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretRef {
+    pub backend: SecretBackend,
+    pub account: String,
 }
 
 /// 余额信息
@@ -30,24 +145,48 @@ pub struct BalanceInfo {
     pub exceeded: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiry_date: Option<String>,
+    /// 计费周期开始时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period_start: Option<String>,
+    /// 各档位用量（standard、premium 等），上面的汇总字段来自 standard
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<TierUsage>,
+    /// 接口原始响应（只保留在内存中用于排查问题，不写入配置、余额历史和前端事件；
+    /// 旧配置中已保存的原始响应在下次保存时丢弃）
+    #[serde(default, skip_serializing)]
+    pub raw: Option<serde_json::Value>,
+}
+
+/// 单个用量档位的用量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TierUsage {
+    /// 档位名称（接口中的字段名，如 standard、premium）
+    pub name: String,
+    pub used: u64,
+    pub allowance: u64,
+    pub remaining: u64,
+    pub overage: u64,
+    pub used_ratio: f64,
+    /// 组织在该档位的总用量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_used: Option<u64>,
+    /// 组织超额用量上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overage_limit: Option<u64>,
 }
 
 /// 推理级别
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ReasoningLevel {
     Off,
     Low,
+    #[default]
     Medium,
     High,
 }
 
-impl Default for ReasoningLevel {
-    fn default() -> Self {
-        ReasoningLevel::Medium
-    }
-}
-
 /// 模型信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -61,6 +200,15 @@ pub struct ModelInfo {
     pub is_builtin: bool,
     #[serde(default)]
     pub reasoning_level: ReasoningLevel,
+    /// 计费倍率（如 1.2 表示每个 token 按 1.2 个计入额度，未知时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_multiplier: Option<f64>,
+    /// 允许的推理级别（为空表示不限制）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasoning_levels: Vec<ReasoningLevel>,
+    /// 内置模型已从新版本的内置列表中移除（保留以免影响已选中该模型的配置）
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 /// 模型配置
@@ -73,11 +221,14 @@ pub struct ModelConfig {
     pub selected_model_id: Option<String>,
 }
 
+/// 默认选中的模型
+pub const DEFAULT_MODEL_ID: &str = "claude-sonnet-4-5-20250929";
+
 impl Default for ModelConfig {
     fn default() -> Self {
         Self {
             available_models: get_builtin_models(),
-            selected_model_id: Some("claude-sonnet-4-5-20250929".to_string()),
+            selected_model_id: Some(DEFAULT_MODEL_ID.to_string()),
         }
     }
 }
@@ -86,6 +237,9 @@ impl Default for ModelConfig {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
+    /// 配置结构版本，加载时由 migrations 升级到当前版本
+    #[serde(default)]
+    pub schema_version: u32,
     #[serde(default)]
     pub providers: Vec<Provider>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -94,6 +248,683 @@ pub struct AppConfig {
     pub last_balance_check: Option<String>,
     #[serde(default)]
     pub model_config: ModelConfig,
+    /// 密钥库配置（为空表示未启用加密，API Key 以明文保存）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault: Option<VaultConfig>,
+    /// 新添加的 API Key 使用的存储后端
+    #[serde(default)]
+    pub secret_backend: SecretBackend,
+    /// 自动轮换策略
+    #[serde(default)]
+    pub rotation_policy: RotationPolicy,
+    /// 已登记的项目目录（用于展示哪些项目覆盖了全局选择）
+    #[serde(default)]
+    pub project_dirs: Vec<String>,
+    /// 正在运行的项目会话（按开始时间排序）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub project_sessions: Vec<ProjectSession>,
+    /// Factory.ai API 地址（为空使用默认地址，可被环境变量 FACTORY_API_BASE_URL 覆盖）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base_url: Option<String>,
+    /// 批量余额查询设置
+    #[serde(default)]
+    pub balance_check: BalanceCheckSettings,
+    /// 后台定时刷新余额设置
+    #[serde(default)]
+    pub balance_refresh: BalanceRefreshSettings,
+    /// 额度提醒设置
+    #[serde(default)]
+    pub quota_alerts: QuotaAlertSettings,
+    /// 到期提醒设置
+    #[serde(default)]
+    pub expiry: ExpirySettings,
+    /// 网络设置（代理、证书、超时）
+    #[serde(default)]
+    pub network: NetworkSettings,
+    /// 外部模型清单设置
+    #[serde(default)]
+    pub model_catalog: ModelCatalogSettings,
+}
+
+/// 项目会话：shell 包装函数在项目目录中运行的 droid 及其使用的模型设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSession {
+    /// 包装函数生成的会话 ID
+    pub id: String,
+    pub project_dir: String,
+    /// 运行包装函数的 shell 进程 ID（进程已退出的会话会被清理）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// 项目指定的模型（为空沿用全局设置）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    /// 项目生效的推理级别（为空沿用全局设置）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_level: Option<ReasoningLevel>,
+    pub started_at: String,
+}
+
+/// 按模型倍率折算的剩余额度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelQuota {
+    pub model_id: String,
+    pub model_name: String,
+    pub cost_multiplier: f64,
+    /// 剩余额度可用于该模型的 tokens
+    pub effective_remaining: u64,
+}
+
+/// 外部模型清单设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelCatalogSettings {
+    /// 模型清单来源：本地文件路径或 HTTP(S) 地址（为空使用内置模型列表）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// 自动重新获取清单的间隔（小时）
+    #[serde(default = "default_catalog_refresh_hours")]
+    pub refresh_hours: u64,
+}
+
+fn default_catalog_refresh_hours() -> u64 {
+    24
+}
+
+impl Default for ModelCatalogSettings {
+    fn default() -> Self {
+        Self {
+            source: None,
+            refresh_hours: default_catalog_refresh_hours(),
+        }
+    }
+}
+
+/// 模型清单（统一发布的模型目录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelManifest {
+    /// 清单结构版本
+    pub version: u32,
+    pub models: Vec<ManifestModel>,
+}
+
+/// 模型清单中的模型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestModel {
+    pub id: String,
+    pub name: String,
+    pub provider: String,
+    /// 计费倍率（如 1.2 表示 1.2x）
+    pub multiplier: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 默认推理级别
+    #[serde(default)]
+    pub default_reasoning: ReasoningLevel,
+    /// 允许的推理级别（为空表示不限制）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasoning_levels: Vec<ReasoningLevel>,
+}
+
+/// 模型清单缓存（保存最近一次成功获取的清单）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelCatalogCache {
+    pub source: String,
+    pub fetched_at: String,
+    pub manifest: ModelManifest,
+}
+
+/// 当前使用的模型目录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelCatalogStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// 清单版本（使用内置模型列表时为空）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    pub model_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    /// 是否在使用内置模型列表（未配置清单或清单尚未成功获取）
+    pub fallback: bool,
+}
+
+/// 网络设置，所有 Factory API 请求共用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkSettings {
+    /// 代理地址（http://、https://、socks5:// 或 socks5h://），为空时使用系统代理环境变量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// 不走代理的主机（如 localhost、.corp.example.com、10.0.0.0/8）
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// 额外信任的根证书（PEM 文件路径，可包含多个证书）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle_path: Option<String>,
+    /// 请求超时（秒）
+    #[serde(default = "default_network_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_network_timeout_secs() -> u64 {
+    15
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: Vec::new(),
+            ca_bundle_path: None,
+            timeout_secs: default_network_timeout_secs(),
+        }
+    }
+}
+
+/// 连接测试失败的环节
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectivityStage {
+    /// 网络设置无效（代理地址、证书文件等）
+    Config,
+    /// 域名解析失败
+    Dns,
+    /// 无法连接代理服务器或代理拒绝转发
+    Proxy,
+    /// 无法建立 TCP 连接
+    Connect,
+    /// TLS 握手或证书校验失败
+    Tls,
+    /// 请求超时
+    Timeout,
+}
+
+/// 连接测试结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityReport {
+    /// 测试的 API 地址
+    pub base_url: String,
+    /// 使用的代理（为空表示直连或系统代理）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// 是否收到 HTTP 响应（任何状态码都说明网络可达）
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub elapsed_ms: u64,
+    /// 失败的环节
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_stage: Option<ConnectivityStage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 批量余额查询设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceCheckSettings {
+    /// 同时查询的密钥数量
+    #[serde(default = "default_balance_concurrency")]
+    pub concurrency: usize,
+    /// 网络错误、5xx 和 429 时的最大重试次数
+    #[serde(default = "default_balance_max_retries")]
+    pub max_retries: u32,
+    /// 首次重试的等待时间（毫秒），之后每次翻倍
+    #[serde(default = "default_balance_retry_delay_ms")]
+    pub retry_base_delay_ms: u64,
+}
+
+fn default_balance_concurrency() -> usize {
+    4
+}
+
+fn default_balance_max_retries() -> u32 {
+    3
+}
+
+fn default_balance_retry_delay_ms() -> u64 {
+    500
+}
+
+impl Default for BalanceCheckSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: default_balance_concurrency(),
+            max_retries: default_balance_max_retries(),
+            retry_base_delay_ms: default_balance_retry_delay_ms(),
+        }
+    }
+}
+
+/// 后台定时刷新余额设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceRefreshSettings {
+    #[serde(default = "default_refresh_enabled")]
+    pub enabled: bool,
+    /// 刷新间隔（分钟）
+    #[serde(default = "default_refresh_interval_minutes")]
+    pub interval_minutes: u64,
+    /// 在此时间（分钟）内查询过的密钥跳过
+    #[serde(default = "default_refresh_ttl_minutes")]
+    pub ttl_minutes: u64,
+    /// 超过此时间（分钟）未成功查询的余额标记为已过时
+    #[serde(default = "default_refresh_stale_after_minutes")]
+    pub stale_after_minutes: u64,
+}
+
+fn default_refresh_enabled() -> bool {
+    true
+}
+
+fn default_refresh_interval_minutes() -> u64 {
+    30
+}
+
+fn default_refresh_ttl_minutes() -> u64 {
+    10
+}
+
+fn default_refresh_stale_after_minutes() -> u64 {
+    120
+}
+
+impl Default for BalanceRefreshSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_refresh_enabled(),
+            interval_minutes: default_refresh_interval_minutes(),
+            ttl_minutes: default_refresh_ttl_minutes(),
+            stale_after_minutes: default_refresh_stale_after_minutes(),
+        }
+    }
+}
+
+/// 额度提醒设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaAlertSettings {
+    #[serde(default = "default_alerts_enabled")]
+    pub enabled: bool,
+    /// 提醒阈值（已用百分比），密钥可单独设置
+    #[serde(default = "default_alert_thresholds")]
+    pub thresholds: Vec<f64>,
+    /// 超额时提醒
+    #[serde(default = "default_alerts_enabled")]
+    pub notify_exceeded: bool,
+    /// 已用百分比回落到阈值以下多少个百分点（如额度重置）后才会再次提醒
+    #[serde(default = "default_alert_hysteresis")]
+    pub hysteresis_percent: f64,
+}
+
+fn default_alerts_enabled() -> bool {
+    true
+}
+
+fn default_alert_thresholds() -> Vec<f64> {
+    vec![50.0, 80.0, 95.0]
+}
+
+fn default_alert_hysteresis() -> f64 {
+    5.0
+}
+
+impl Default for QuotaAlertSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_alerts_enabled(),
+            thresholds: default_alert_thresholds(),
+            notify_exceeded: default_alerts_enabled(),
+            hysteresis_percent: default_alert_hysteresis(),
+        }
+    }
+}
+
+/// 额度提醒（quota-alert 事件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaAlert {
+    pub provider_id: String,
+    pub provider_name: String,
+    /// 越过的阈值（超额提醒时为空）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    pub exceeded: bool,
+    pub percent_used: f64,
+    pub remaining: u64,
+    pub message: String,
+}
+
+/// 到期提醒设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpirySettings {
+    /// 到期前提醒
+    #[serde(default = "default_expiry_enabled")]
+    pub enabled: bool,
+    /// 在到期前多少天提醒
+    #[serde(default = "default_expiry_warn_days")]
+    pub warn_days: Vec<u32>,
+    /// 当前密钥过期时自动切换到其他密钥
+    #[serde(default = "default_expiry_enabled")]
+    pub switch_when_expired: bool,
+}
+
+fn default_expiry_enabled() -> bool {
+    true
+}
+
+fn default_expiry_warn_days() -> Vec<u32> {
+    vec![7, 1]
+}
+
+impl Default for ExpirySettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_expiry_enabled(),
+            warn_days: default_expiry_warn_days(),
+            switch_when_expired: default_expiry_enabled(),
+        }
+    }
+}
+
+/// 到期提醒（expiry-alert 事件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryAlert {
+    pub provider_id: String,
+    pub provider_name: String,
+    pub expiry_date: String,
+    /// 距到期的整天数（已过期时为负数或 0）
+    pub days_until_expiry: i64,
+    pub expired: bool,
+    pub message: String,
+}
+
+/// 余额刷新进度（balance-updated 事件）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceUpdatedEvent {
+    pub provider_id: String,
+    pub provider_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+    /// 本轮已完成的密钥数
+    pub completed: usize,
+    /// 本轮需要查询的密钥数
+    pub total: usize,
+}
+
+/// 单个密钥的余额查询结果（成功时有 balance，失败时有 error）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceCheckResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+impl From<Result<BalanceInfo, AppError>> for BalanceCheckResult {
+    fn from(result: Result<BalanceInfo, AppError>) -> Self {
+        match result {
+            Ok(balance) => Self {
+                balance: Some(balance),
+                error: None,
+            },
+            Err(e) => Self {
+                balance: None,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+/// 刷新所有密钥余额的结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshAllResult {
+    /// 更新后的密钥列表
+    pub providers: Vec<Provider>,
+    /// 查询失败的密钥：密钥 ID → 失败原因
+    pub errors: HashMap<String, AppError>,
+}
+
+/// 自动轮换候选排序方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RotationOrder {
+    /// 剩余额度最多的优先
+    #[default]
+    MostRemaining,
+    /// 最早到期的优先（先用掉快过期的额度）
+    SoonestExpiry,
+    /// 按 manual_order 中的顺序
+    Manual,
+}
+
+/// 自动轮换策略：当前密钥用量超过阈值或已超额时切换到候选密钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationPolicy {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 触发轮换的已用百分比 (0-100)
+    #[serde(default = "default_rotation_threshold")]
+    pub threshold_percent: f64,
+    #[serde(default)]
+    pub order: RotationOrder,
+    /// 手动排序时的密钥 ID 顺序
+    #[serde(default)]
+    pub manual_order: Vec<String>,
+    /// 不参与轮换的密钥 ID
+    #[serde(default)]
+    pub excluded_ids: Vec<String>,
+}
+
+fn default_rotation_threshold() -> f64 {
+    95.0
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_percent: default_rotation_threshold(),
+            order: RotationOrder::default(),
+            manual_order: Vec::new(),
+            excluded_ids: Vec::new(),
+        }
+    }
+}
+
+/// 密钥库配置：Argon2id 派生密钥参数及口令校验串
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultConfig {
+    pub version: u32,
+    /// Argon2 盐值（base64）
+    pub salt: String,
+    /// 用派生密钥加密的固定明文，用于校验口令
+    pub verifier: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+/// 密钥库状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+
+/// 密钥导入导出格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferFormat {
+    /// 完整的 Provider 数组
+    Json,
+    /// name,api_key,notes
+    Csv,
+    /// NAME=fk-...
+    Dotenv,
+}
+
+/// 单行导入结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStatus {
+    Added,
+    SkippedDuplicate,
+    Invalid,
+}
+
+/// 导入报告中的一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRow {
+    /// 行号（JSON 为数组下标，从 1 开始）
+    pub line: usize,
+    pub name: String,
+    pub status: ImportStatus,
+    /// 新增密钥的 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_id: Option<String>,
+    /// 跳过或无效的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// 导入报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+    pub added: usize,
+    pub skipped: usize,
+    pub invalid: usize,
+}
+
+/// 审计操作类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    SwitchProvider,
+    DisableProvider,
+    AddProvider,
+    RemoveProvider,
+    SetSelectedModel,
+    RefreshBalance,
+}
+
+/// 操作来源
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditSource {
+    /// 托盘菜单
+    Tray,
+    /// 主界面
+    Ui,
+    /// 命令行
+    Cli,
+    /// 自动轮换等后台任务
+    Auto,
+}
+
+/// 操作结果
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// 审计日志记录（audit.jsonl 中的一行，不包含 API Key）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub action: AuditAction,
+    pub source: AuditSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    pub outcome: AuditOutcome,
+    /// 补充信息（如轮换原因、查询到的余额）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// 失败原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 审计日志查询条件，均为可选
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub source: Option<AuditSource>,
+    pub outcome: Option<AuditOutcome>,
+    /// 密钥 ID 或名称
+    pub provider: Option<String>,
+    /// 起始时间（RFC 3339，含）
+    pub since: Option<String>,
+    /// 截止时间（RFC 3339，含）
+    pub until: Option<String>,
+    /// 最多返回条数（从最新的记录开始）
+    pub limit: Option<usize>,
+}
+
+/// 余额快照（balance_history.jsonl 中的一行）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceSnapshot {
+    pub timestamp: String,
+    pub provider_id: String,
+    pub balance: BalanceInfo,
+}
+
+/// 余额历史查询范围，均为可选
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRange {
+    /// 起始时间（RFC 3339，含）
+    pub since: Option<String>,
+    /// 截止时间（RFC 3339，含）
+    pub until: Option<String>,
+}
+
+/// 余额消耗预测
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceForecast {
+    pub provider_id: String,
+    /// 参与计算的快照数量
+    pub sample_count: usize,
+    /// 每日消耗 tokens（样本不足时为空）
+    pub daily_burn_rate: Option<f64>,
+    pub remaining: Option<u64>,
+    /// 按当前速度预计用完的时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projected_exhaustion_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<String>,
+    /// 是否会在到期前用完（无法判断时为空）
+    pub exhausts_before_expiry: Option<bool>,
 }
 
 /// 获取内置模型列表
@@ -103,65 +934,89 @@ pub fn get_builtin_models() -> Vec<ModelInfo> {
             id: "claude-sonnet-4-5-20250929".to_string(),
             name: "Claude Sonnet 4.5".to_string(),
             provider: "Anthropic".to_string(),
-            description: Some("1.2x - 日常开发默认选择".to_string()),
+            description: Some("日常开发默认选择".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Medium,
+            cost_multiplier: Some(1.2),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "claude-opus-4-5-20251101".to_string(),
             name: "Claude Opus 4.5".to_string(),
             provider: "Anthropic".to_string(),
-            description: Some("1.2x - 高级推理模型".to_string()),
+            description: Some("高级推理模型".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::High,
+            cost_multiplier: Some(1.2),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "claude-opus-4-1-20250805".to_string(),
             name: "Claude Opus 4.1".to_string(),
             provider: "Anthropic".to_string(),
-            description: Some("6x - 复杂架构决策".to_string()),
+            description: Some("复杂架构决策".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::High,
+            cost_multiplier: Some(6.0),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "claude-haiku-4-5-20251001".to_string(),
             name: "Claude Haiku 4.5".to_string(),
             provider: "Anthropic".to_string(),
-            description: Some("0.4x - 快速、高性价比".to_string()),
+            description: Some("快速、高性价比".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Low,
+            cost_multiplier: Some(0.4),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "gpt-5.1-codex".to_string(),
             name: "GPT-5.1-Codex".to_string(),
             provider: "OpenAI".to_string(),
-            description: Some("0.5x - 编码任务优化".to_string()),
+            description: Some("编码任务优化".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Medium,
+            cost_multiplier: Some(0.5),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "gpt-5.1".to_string(),
             name: "GPT-5.1".to_string(),
             provider: "OpenAI".to_string(),
-            description: Some("0.5x - OpenAI 通用模型".to_string()),
+            description: Some("OpenAI 通用模型".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Medium,
+            cost_multiplier: Some(0.5),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "gemini-3-pro-preview".to_string(),
             name: "Gemini 3 Pro".to_string(),
             provider: "Google".to_string(),
-            description: Some("0.8x - Google 多模态模型".to_string()),
+            description: Some("Google 多模态模型".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Medium,
+            cost_multiplier: Some(0.8),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
         ModelInfo {
             id: "glm-4.6".to_string(),
             name: "Droid Core (GLM-4.6)".to_string(),
             provider: "智谱AI".to_string(),
-            description: Some("0.25x - 开源、离线环境".to_string()),
+            description: Some("开源、离线环境".to_string()),
             is_builtin: true,
             reasoning_level: ReasoningLevel::Low,
+            cost_multiplier: Some(0.25),
+            reasoning_levels: Vec::new(),
+            deprecated: false,
         },
     ]
 }
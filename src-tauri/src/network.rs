@@ -0,0 +1,177 @@
+use crate::config::run_blocking;
+use crate::error::AppError;
+use crate::factory_client::resolve_base_url;
+use crate::models::{ConnectivityReport, ConnectivityStage, NetworkSettings};
+use crate::provider_manager::{load_config, update_config};
+use std::error::Error as _;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 共享的 HTTP 客户端及创建它时使用的网络设置
+static SHARED_CLIENT: Mutex<Option<(NetworkSettings, reqwest::Client)>> = Mutex::new(None);
+
+/// 获取网络设置
+pub fn get_settings() -> Result<NetworkSettings, AppError> {
+    Ok(load_config()?.network)
+}
+
+/// 保存网络设置（保存前校验代理地址和证书文件）
+pub fn set_settings(mut settings: NetworkSettings) -> Result<(), AppError> {
+    settings.proxy = settings
+        .proxy
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    settings.ca_bundle_path = settings
+        .ca_bundle_path
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    settings.no_proxy = settings
+        .no_proxy
+        .iter()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .collect();
+    build_client(&settings)?;
+
+    update_config(|config| {
+        config.network = settings;
+        Ok(())
+    })?;
+    log::info!("已更新网络设置");
+    Ok(())
+}
+
+/// 按网络设置创建 HTTP 客户端
+pub fn build_client(settings: &NetworkSettings) -> Result<reqwest::Client, AppError> {
+    if settings.timeout_secs == 0 {
+        return Err(AppError::invalid_input("请求超时必须大于 0 秒"));
+    }
+    let mut builder =
+        reqwest::Client::builder().timeout(Duration::from_secs(settings.timeout_secs));
+
+    if let Some(url) = &settings.proxy {
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| AppError::invalid_input(format!("无效的代理地址 {}: {}", url, e)))?
+            .no_proxy(reqwest::NoProxy::from_string(&settings.no_proxy.join(",")));
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &settings.ca_bundle_path {
+        let pem = std::fs::read(path)
+            .map_err(|e| AppError::io(&format!("读取证书文件 {} 失败", path), e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| AppError::parse(&format!("解析证书文件 {} 失败", path), e))?;
+        if certs.is_empty() {
+            return Err(AppError::Parse(format!("证书文件 {} 中没有证书", path)));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| AppError::other(format!("创建 HTTP 客户端失败: {}", e)))
+}
+
+/// 获取共享的 HTTP 客户端（网络设置不变时复用同一个客户端和连接池）
+pub fn shared_client(settings: &NetworkSettings) -> Result<reqwest::Client, AppError> {
+    let mut shared = SHARED_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, client)) = shared.as_ref() {
+        if cached == settings {
+            return Ok(client.clone());
+        }
+    }
+
+    let client = build_client(settings)?;
+    *shared = Some((settings.clone(), client.clone()));
+    Ok(client)
+}
+
+/// 使用当前配置测试能否连接 Factory API
+pub async fn test_connectivity() -> Result<ConnectivityReport, AppError> {
+    let config = run_blocking(load_config).await?;
+    Ok(test_connectivity_with(&resolve_base_url(&config), &config.network).await)
+}
+
+/// 使用指定地址和网络设置测试连接
+///
+/// 收到任何 HTTP 响应（包括 401 等错误状态）都说明网络可达；失败时报告出错的环节。
+pub async fn test_connectivity_with(
+    base_url: &str,
+    settings: &NetworkSettings,
+) -> ConnectivityReport {
+    let mut report = ConnectivityReport {
+        base_url: base_url.to_string(),
+        proxy: settings.proxy.clone(),
+        ok: false,
+        status: None,
+        elapsed_ms: 0,
+        failed_stage: None,
+        error: None,
+    };
+
+    let client = match build_client(settings) {
+        Ok(client) => client,
+        Err(e) => {
+            report.failed_stage = Some(ConnectivityStage::Config);
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    let started = Instant::now();
+    let result = client.get(base_url).send().await;
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(response) => {
+            report.ok = true;
+            report.status = Some(response.status().as_u16());
+            log::info!("连接测试成功: {} 返回 {}", base_url, response.status());
+        }
+        Err(e) => {
+            let message = error_chain(&e);
+            report.failed_stage = Some(classify(&e, &message, settings.proxy.is_some()));
+            log::warn!("连接测试失败: {}", message);
+            report.error = Some(message);
+        }
+    }
+    report
+}
+
+/// 拼接错误及其所有底层原因（reqwest 的顶层错误信息通常不包含具体原因）
+fn error_chain(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// 根据错误信息判断失败的环节（reqwest 不区分 DNS、代理和 TLS 错误，只能按原因文本判断）
+///
+/// 配置了代理时，连接和域名解析都经过代理，这类失败都归为代理问题。
+fn classify(e: &reqwest::Error, message: &str, proxied: bool) -> ConnectivityStage {
+    if e.is_timeout() {
+        return ConnectivityStage::Timeout;
+    }
+    if e.is_builder() {
+        return ConnectivityStage::Config;
+    }
+
+    let message = message.to_lowercase();
+    let contains_any = |words: &[&str]| words.iter().any(|w| message.contains(w));
+    if contains_any(&["certificate", "tls", "ssl", "handshake"]) {
+        ConnectivityStage::Tls
+    } else if proxied {
+        ConnectivityStage::Proxy
+    } else if contains_any(&["dns", "lookup", "resolve", "name or service not known"]) {
+        ConnectivityStage::Dns
+    } else {
+        ConnectivityStage::Connect
+    }
+}
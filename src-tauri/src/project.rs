@@ -0,0 +1,195 @@
+use crate::error::AppError;
+use crate::models::{AppConfig, ReasoningLevel};
+use crate::provider_manager::{find_provider, load_config, update_config};
+use crate::secret_store::resolve_api_key;
+use crate::vault;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 项目绑定文件名
+pub const PROJECT_FILE_NAME: &str = ".droid-switch.toml";
+
+/// 项目绑定文件内容
+///
+/// ```toml
+/// provider = "client-a"
+/// model = "claude-opus-4-5-20251101"
+/// reasoning = "high"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectFile {
+    /// 密钥名称或 ID
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub reasoning: Option<ReasoningLevel>,
+}
+
+/// 项目绑定解析结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectBinding {
+    /// 项目根目录（绑定文件所在目录）
+    pub project_dir: String,
+    pub file_path: String,
+    /// 绑定文件中填写的密钥名称或 ID
+    pub provider: String,
+    /// 解析到的密钥 ID（未找到时为空）
+    pub provider_id: Option<String>,
+    pub provider_name: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<ReasoningLevel>,
+    /// 是否与全局选择不同
+    pub overrides_global: bool,
+    /// 解析失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 从 start 开始逐级向上查找项目绑定文件
+pub fn find_project_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// 读取项目绑定文件
+pub fn read_project_file(path: &Path) -> Result<ProjectFile, AppError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| AppError::io(&format!("读取 {} 失败", path.display()), e))?;
+    toml::from_str(&content)
+        .map_err(|e| AppError::parse(&format!("解析 {} 失败", path.display()), e))
+}
+
+/// 根据配置解析绑定文件
+fn bind(config: &AppConfig, path: &Path) -> ProjectBinding {
+    bind_checked(config, path).0
+}
+
+/// 根据配置解析绑定文件，同时返回解析失败的原始错误
+fn bind_checked(config: &AppConfig, path: &Path) -> (ProjectBinding, Option<AppError>) {
+    let project_dir = path
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut binding = ProjectBinding {
+        project_dir,
+        file_path: path.to_string_lossy().to_string(),
+        provider: String::new(),
+        provider_id: None,
+        provider_name: None,
+        model: None,
+        reasoning: None,
+        overrides_global: false,
+        error: None,
+    };
+
+    let file = match read_project_file(path) {
+        Ok(file) => file,
+        Err(e) => {
+            binding.error = Some(e.to_string());
+            return (binding, Some(e));
+        }
+    };
+
+    let error = match find_provider(config, &file.provider) {
+        Ok(provider) => {
+            binding.provider_id = Some(provider.id.clone());
+            binding.provider_name = Some(provider.name.clone());
+            None
+        }
+        Err(e) => {
+            binding.error = Some(e.to_string());
+            Some(e)
+        }
+    };
+
+    binding.overrides_global = binding.provider_id != config.active_provider_id
+        || file
+            .model
+            .as_ref()
+            .is_some_and(|m| config.model_config.selected_model_id.as_ref() != Some(m))
+        || file.reasoning.is_some();
+    binding.provider = file.provider;
+    binding.model = file.model;
+    binding.reasoning = file.reasoning;
+    (binding, error)
+}
+
+/// 解析目录（或其上级目录）的项目绑定
+pub fn resolve(dir: &Path) -> Result<Option<ProjectBinding>, AppError> {
+    let Some(path) = find_project_file(dir) else {
+        return Ok(None);
+    };
+    let config = load_config()?;
+    Ok(Some(bind(&config, &path)))
+}
+
+/// 解析目录对应项目绑定的 API Key
+///
+/// 找到绑定文件但无法解析出密钥时返回错误，绝不回退到全局密钥。
+pub fn resolve_api_key_for_dir(dir: &Path) -> Result<Option<(ProjectBinding, String)>, AppError> {
+    let Some(path) = find_project_file(dir) else {
+        return Ok(None);
+    };
+
+    let config = load_config()?;
+    vault::ensure_unlocked(&config)?;
+    let (binding, error) = bind_checked(&config, &path);
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    let provider_id = binding.provider_id.as_deref().unwrap_or_default();
+    let provider = find_provider(&config, provider_id)?;
+    let api_key = resolve_api_key(provider)?;
+    Ok(Some((binding, api_key)))
+}
+
+/// 列出已登记项目的绑定情况
+pub fn list_project_bindings() -> Result<Vec<ProjectBinding>, AppError> {
+    let config = load_config()?;
+    Ok(config
+        .project_dirs
+        .iter()
+        .filter_map(|dir| {
+            let path = Path::new(dir).join(PROJECT_FILE_NAME);
+            if path.is_file() {
+                Some(bind(&config, &path))
+            } else {
+                log::warn!("项目目录中未找到 {}: {}", PROJECT_FILE_NAME, dir);
+                None
+            }
+        })
+        .collect())
+}
+
+/// 登记项目目录
+pub fn add_project_dir(dir: String) -> Result<ProjectBinding, AppError> {
+    let path = Path::new(&dir).join(PROJECT_FILE_NAME);
+    if !path.is_file() {
+        return Err(AppError::not_found(PROJECT_FILE_NAME, dir));
+    }
+
+    update_config(|config| {
+        if !config.project_dirs.contains(&dir) {
+            config.project_dirs.push(dir.clone());
+            log::info!("已登记项目目录: {}", dir);
+        }
+        Ok(bind(config, &path))
+    })
+}
+
+/// 取消登记项目目录
+pub fn remove_project_dir(dir: String) -> Result<(), AppError> {
+    update_config(|config| {
+        config.project_dirs.retain(|d| d != &dir);
+        Ok(())
+    })?;
+    log::info!("已取消登记项目目录: {}", dir);
+    Ok(())
+}
@@ -1,10 +1,22 @@
-use crate::config::{get_app_config_path, read_json_file, write_json_file};
+use crate::balance;
+use crate::balance_history;
+use crate::config::{get_app_config_path, lock_app_config, write_json_file};
 use crate::droid_config::{clear_factory_api_key_env, set_factory_api_key_env};
-use crate::models::{AppConfig, Provider};
+use crate::error::AppError;
+use crate::health;
+use crate::migrations;
+use crate::model_catalog;
+use crate::model_manager;
+use crate::models::{
+    AppConfig, BalanceInfo, Provider, ProviderStatus, QuotaAlertState, SecretBackend, VaultStatus,
+};
+use crate::secret_store::{self, resolve_api_key};
+use crate::vault;
 use chrono::Utc;
+use std::collections::HashMap;
 
 /// 加载应用配置
-pub fn load_config() -> Result<AppConfig, String> {
+pub fn load_config() -> Result<AppConfig, AppError> {
     let config_path = get_app_config_path();
 
     if !config_path.exists() {
@@ -12,164 +24,480 @@ pub fn load_config() -> Result<AppConfig, String> {
         return Ok(AppConfig::default());
     }
 
-    read_json_file(&config_path)
+    // 先按结构版本迁移原始 JSON，再反序列化（迁移可能写回文件，需持有配置锁）
+    let raw = {
+        let _lock = lock_app_config()?;
+        migrations::load_and_migrate(&config_path)?
+    };
+    let mut config: AppConfig =
+        serde_json::from_value(raw).map_err(|e| AppError::parse("解析配置失败", e))?;
+    vault::decrypt_providers(&mut config)?;
+    balance::mark_stale(&mut config, Utc::now());
+    let catalog = model_catalog::catalog_models(&config.model_catalog);
+    model_manager::merge_builtin_models(&mut config.model_config.available_models, catalog);
+    Ok(config)
 }
 
 /// 保存应用配置（原子写入）
-pub fn save_config(config: &AppConfig) -> Result<(), String> {
+///
+/// 启用密钥库时 API Key 会先加密再写入磁盘。修改配置请使用 `update_config`。
+fn save_config(config: &AppConfig) -> Result<(), AppError> {
     let config_path = get_app_config_path();
-    write_json_file(&config_path, config)
+    let mut on_disk = vault::encrypt_providers(config)?;
+    on_disk.schema_version = migrations::CURRENT_SCHEMA_VERSION;
+    // 过时标记在加载时重新计算
+    for provider in &mut on_disk.providers {
+        provider.balance_stale = false;
+    }
+    write_json_file(&config_path, &on_disk)
+}
+
+/// 事务式修改配置：加锁 → 读取 → 修改 → 保存
+///
+/// 所有对配置的修改都应通过此函数完成，避免桌面应用与命令行同时写入时
+/// 互相覆盖。闭包返回错误时不保存。闭包内不要执行网络请求等耗时操作。
+pub fn update_config<T>(
+    f: impl FnOnce(&mut AppConfig) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let _lock = lock_app_config()?;
+    let mut config = load_config()?;
+    let result = f(&mut config)?;
+    save_config(&config)?;
+    Ok(result)
 }
 
-/// 添加密钥
-pub fn add_provider(name: String, api_key: String) -> Result<Provider, String> {
+/// 加载应用配置，并从外部存储中解析出所有 API Key（供界面展示）
+pub fn load_config_with_secrets() -> Result<AppConfig, AppError> {
     let mut config = load_config()?;
+    for provider in &mut config.providers {
+        if provider.secret_ref.is_some() {
+            match resolve_api_key(provider) {
+                Ok(api_key) => provider.api_key = api_key,
+                Err(e) => log::warn!("解析密钥 {} 失败: {}", provider.name, e),
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// 密钥名称最大长度（与前端校验一致）
+pub const MAX_NAME_LEN: usize = 50;
+
+/// 检查密钥名称不为空且不超过最大长度
+pub fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::invalid_input("密钥名称为空"));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(AppError::invalid_input(format!(
+            "密钥名称过长（最多 {} 个字符）",
+            MAX_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// 解析所有密钥的 API Key（按 ID 索引，无法解析的跳过）
+///
+/// 在获取配置锁之前调用，避免持锁期间访问系统钥匙串等外部存储。
+pub fn resolve_known_api_keys() -> Result<HashMap<String, String>, AppError> {
+    Ok(load_config()?
+        .providers
+        .iter()
+        .filter_map(|p| resolve_api_key(p).ok().map(|k| (p.id.clone(), k)))
+        .collect())
+}
 
+/// 按添加密钥的规则检查名称和 API Key 是否重复
+///
+/// known_keys 为 resolve_known_api_keys 预先解析的 API Key；之后新增的密钥才在此处解析。
+pub fn check_duplicate(
+    config: &AppConfig,
+    known_keys: &HashMap<String, String>,
+    name: &str,
+    api_key: &str,
+) -> Result<(), AppError> {
     // 检查是否已存在同名密钥
     if config.providers.iter().any(|p| p.name == name) {
-        return Err(format!("密钥名称 '{}' 已存在", name));
+        return Err(AppError::duplicate(
+            "name",
+            format!("密钥名称 '{}' 已存在", name),
+        ));
     }
 
     // 检查 API Key 是否已存在
-    if config.providers.iter().any(|p| p.api_key == api_key) {
-        return Err("此 API Key 已被添加".to_string());
+    if config
+        .providers
+        .iter()
+        .any(|p| match known_keys.get(&p.id) {
+            Some(key) => key == api_key,
+            None => resolve_api_key(p).is_ok_and(|k| k == api_key),
+        })
+    {
+        return Err(AppError::duplicate("apiKey", "此 API Key 已被添加"));
     }
 
-    let now = Utc::now().to_rfc3339();
-    let provider = Provider {
-        id: uuid::Uuid::new_v4().to_string(),
-        name,
-        api_key,
-        balance: None,
-        is_active: false,
-        created_at: Some(now.clone()),
-        updated_at: Some(now),
-    };
+    Ok(())
+}
 
-    config.providers.push(provider.clone());
-    save_config(&config)?;
+/// 添加密钥（添加前检查名称和 API Key 格式）
+pub fn add_provider(name: String, api_key: String) -> Result<Provider, AppError> {
+    validate_name(&name)?;
+    let api_key = api_key.trim().to_string();
+    health::validate_key_format(&api_key)?;
+
+    let known_keys = resolve_known_api_keys()?;
+    let provider = update_config(|config| {
+        vault::ensure_unlocked(config)?;
+        check_duplicate(config, &known_keys, &name, &api_key)?;
+
+        let now = Utc::now().to_rfc3339();
+        let mut provider = Provider {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            notes: None,
+            api_key: String::new(),
+            encrypted_api_key: None,
+            secret_ref: None,
+            balance: None,
+            balance_checked_at: None,
+            balance_stale: false,
+            last_error: None,
+            last_error_at: None,
+            consecutive_failures: 0,
+            is_active: false,
+            expired: false,
+            status: ProviderStatus::Unknown,
+            created_at: Some(now.clone()),
+            updated_at: Some(now),
+            alert_thresholds: None,
+            alert_state: QuotaAlertState::default(),
+        };
+
+        // 通过配置的存储后端保存 API Key
+        secret_store::store_for_backend(config.secret_backend).put(&mut provider, &api_key)?;
+
+        config.providers.push(provider.clone());
+        Ok(provider)
+    })?;
 
     log::info!("已添加密钥: {}", provider.name);
     Ok(provider)
 }
 
 /// 删除密钥
-pub fn remove_provider(id: String) -> Result<(), String> {
-    let mut config = load_config()?;
-
-    let provider_index = config
-        .providers
-        .iter()
-        .position(|p| p.id == id)
-        .ok_or_else(|| format!("未找到 ID 为 {} 的密钥", id))?;
-
-    let provider = &config.providers[provider_index];
+///
+/// 持有配置锁完成配置保存（删除的是当前激活的密钥时同时清除 ~/.factory/config.json），
+/// 释放锁后再删除存储后端中的 API Key，避免在锁内访问系统钥匙串。
+pub fn remove_provider(id: String) -> Result<(), AppError> {
+    let removed = {
+        let _lock = lock_app_config()?;
+        let (removed, was_active) = update_config(|config| {
+            let provider_index = config
+                .providers
+                .iter()
+                .position(|p| p.id == id)
+                .ok_or_else(|| AppError::not_found("密钥", id.clone()))?;
+
+            let was_active = config.active_provider_id.as_ref() == Some(&id);
+            if was_active {
+                config.active_provider_id = None;
+            }
+            Ok((config.providers.remove(provider_index), was_active))
+        })?;
+
+        // 删除的是当前激活的密钥时，配置保存成功后再清除环境变量
+        if was_active {
+            clear_factory_api_key_env()?;
+            log::info!("已清除当前激活的密钥环境变量");
+        }
+        removed
+    };
 
-    // 如果删除的是当前激活的密钥，先清除环境变量
-    if config.active_provider_id.as_ref() == Some(&id) {
-        clear_factory_api_key_env()?;
-        config.active_provider_id = None;
-        log::info!("已清除当前激活的密钥环境变量");
+    // 从存储后端删除 API Key
+    if let Err(e) = secret_store::store_for(&removed).delete(&removed) {
+        log::warn!("删除密钥 {} 的存储失败: {}", removed.name, e);
     }
 
-    let name = provider.name.clone();
-    config.providers.remove(provider_index);
-    save_config(&config)?;
-
-    log::info!("已删除密钥: {}", name);
+    log::info!("已删除密钥: {}", removed.name);
     Ok(())
 }
 
 /// 切换密钥
-pub fn switch_provider(id: String) -> Result<Provider, String> {
-    let mut config = load_config()?;
-
-    // 先找到密钥并复制 API Key
-    let api_key = config
-        .providers
-        .iter()
-        .find(|p| p.id == id)
-        .ok_or_else(|| format!("未找到 ID 为 {} 的密钥", id))?
-        .api_key
-        .clone();
+///
+/// 配置保存成功后再写入 ~/.factory/config.json；期间一直持有配置锁，
+/// 其他进程的切换不会与之交错。
+pub fn switch_provider(id: String) -> Result<Provider, AppError> {
+    let _lock = lock_app_config()?;
+    let (provider, api_key) = update_config(|config| {
+        vault::ensure_unlocked(config)?;
+
+        // 先找到密钥并从存储后端解析 API Key
+        let api_key = config
+            .providers
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::not_found("密钥", id.clone()))
+            .and_then(resolve_api_key)?;
+
+        // 更新配置：取消所有激活状态，激活选中的密钥
+        for p in &mut config.providers {
+            p.is_active = p.id == id;
+        }
+        config.active_provider_id = Some(id.clone());
+
+        // 返回激活的密钥
+        let provider = config
+            .providers
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .unwrap();
+        Ok((provider, api_key))
+    })?;
 
     // 设置系统环境变量
     set_factory_api_key_env(&api_key)?;
 
-    // 更新配置：取消所有激活状态，激活选中的密钥
-    for p in &mut config.providers {
-        p.is_active = p.id == id;
-    }
-    config.active_provider_id = Some(id.clone());
-    save_config(&config)?;
-
-    // 返回激活的密钥
-    let provider = config
-        .providers
-        .into_iter()
-        .find(|p| p.id == id)
-        .unwrap();
-
     log::info!("已切换到密钥: {}", provider.name);
     Ok(provider)
 }
 
 /// 停用当前密钥
-pub fn disable_provider() -> Result<(), String> {
-    let mut config = load_config()?;
+pub fn disable_provider() -> Result<(), AppError> {
+    let _lock = lock_app_config()?;
+    let disabled = update_config(|config| {
+        if config.active_provider_id.is_none() {
+            return Ok(false);
+        }
+
+        // 更新配置：取消所有激活状态
+        for p in &mut config.providers {
+            p.is_active = false;
+        }
+        config.active_provider_id = None;
+        Ok(true)
+    })?;
 
-    if config.active_provider_id.is_none() {
+    if !disabled {
         log::info!("当前没有激活的密钥");
         return Ok(());
     }
 
-    // 清除环境变量
+    // 配置保存成功后再清除环境变量
     clear_factory_api_key_env()?;
-
-    // 更新配置：取消所有激活状态
-    for p in &mut config.providers {
-        p.is_active = false;
-    }
-    config.active_provider_id = None;
-    save_config(&config)?;
-
     log::info!("已停用当前密钥");
     Ok(())
 }
 
+/// 按 ID 或名称查找密钥（ID 优先）
+pub fn find_provider<'a>(
+    config: &'a AppConfig,
+    name_or_id: &str,
+) -> Result<&'a Provider, AppError> {
+    config
+        .providers
+        .iter()
+        .find(|p| p.id == name_or_id)
+        .or_else(|| config.providers.iter().find(|p| p.name == name_or_id))
+        .ok_or_else(|| AppError::not_found("密钥", name_or_id))
+}
+
 /// 获取当前激活的密钥
-pub fn get_active_provider() -> Result<Option<Provider>, String> {
+pub fn get_active_provider() -> Result<Option<Provider>, AppError> {
     let config = load_config()?;
 
     if let Some(active_id) = config.active_provider_id {
-        let provider = config
-            .providers
-            .into_iter()
-            .find(|p| p.id == active_id);
+        let provider = config.providers.into_iter().find(|p| p.id == active_id);
         Ok(provider)
     } else {
         Ok(None)
     }
 }
 
-/// 更新密钥余额信息
-pub fn update_provider_balance(
-    id: String,
-    balance: crate::models::BalanceInfo,
-) -> Result<(), String> {
-    let mut config = load_config()?;
+/// 记录余额查询结果（一次写入），跳过已不存在的密钥
+///
+/// 成功时更新余额和查询时间并清除失败记录；失败时保留上次的余额，记录错误和连续失败次数。
+/// 密钥状态按 HTTP 结果更新，网络错误等无法判断时保持不变。
+/// 密钥库锁定导致的失败与密钥本身无关，不计入失败记录。
+pub fn record_balance_results(
+    results: &[(&str, &Result<BalanceInfo, AppError>)],
+) -> Result<(), AppError> {
+    if results.is_empty() {
+        return Ok(());
+    }
 
-    let provider = config
-        .providers
-        .iter_mut()
-        .find(|p| p.id == id)
-        .ok_or_else(|| format!("未找到 ID 为 {} 的密钥", id))?;
+    let now = Utc::now().to_rfc3339();
+    let (succeeded, failed) = update_config(|config| {
+        let (mut succeeded, mut failed) = (Vec::new(), 0);
+        for (id, result) in results {
+            let Some(provider) = config.providers.iter_mut().find(|p| p.id == *id) else {
+                log::warn!("未找到 ID 为 {} 的密钥，跳过余额更新", id);
+                continue;
+            };
+            if let Some(status) = health::status_from_result(result) {
+                provider.status = status;
+            }
+            match result {
+                Ok(balance) => {
+                    provider.balance = Some(balance.clone());
+                    provider.balance_checked_at = Some(now.clone());
+                    provider.balance_stale = false;
+                    provider.updated_at = Some(now.clone());
+                    provider.last_error = None;
+                    provider.last_error_at = None;
+                    provider.consecutive_failures = 0;
+                    succeeded.push((*id, balance));
+                }
+                Err(AppError::Locked(_)) => {}
+                Err(e) => {
+                    provider.last_error = Some(e.to_string());
+                    provider.last_error_at = Some(now.clone());
+                    provider.consecutive_failures += 1;
+                    failed += 1;
+                }
+            }
+        }
+        config.last_balance_check = Some(now.clone());
+        Ok((succeeded, failed))
+    })?;
+
+    // 保留每次查询结果，用于消耗预测
+    for (id, balance) in &succeeded {
+        balance_history::append_snapshot(id, balance);
+    }
+    log::info!(
+        "已记录余额查询结果: {} 个成功, {} 个失败",
+        succeeded.len(),
+        failed
+    );
+    Ok(())
+}
 
-    let provider_name = provider.name.clone();
-    provider.balance = Some(balance);
-    provider.updated_at = Some(Utc::now().to_rfc3339());
+/// 设置 Factory.ai API 地址（传空恢复默认地址）
+pub fn set_api_base_url(url: Option<String>) -> Result<(), AppError> {
+    let url = url
+        .map(|u| u.trim().trim_end_matches('/').to_string())
+        .filter(|u| !u.is_empty());
+    if let Some(u) = &url {
+        if !u.starts_with("http://") && !u.starts_with("https://") {
+            return Err(AppError::invalid_input(format!("无效的 API 地址: {}", u)));
+        }
+    }
 
-    save_config(&config)?;
-    log::info!("已更新密钥 {} 的余额信息", provider_name);
+    update_config(|config| {
+        config.api_base_url = url.clone();
+        Ok(())
+    })?;
+    log::info!("API 地址已设置为: {}", url.as_deref().unwrap_or("默认"));
+    Ok(())
+}
+
+/// 获取密钥库状态
+pub fn get_vault_status() -> Result<VaultStatus, AppError> {
+    let config = load_config()?;
+    Ok(vault::get_status(&config))
+}
+
+/// 启用密钥库并加密现有的明文 API Key
+pub fn enable_vault(passphrase: String) -> Result<(), AppError> {
+    update_config(|config| vault::enable_vault(config, &passphrase))
+}
+
+/// 解锁密钥库
+pub fn unlock_vault(passphrase: String) -> Result<(), AppError> {
+    let config = load_config()?;
+    vault::unlock_vault(&config, &passphrase)
+}
+
+/// 启用了密钥库但未解锁时返回错误（余额查询等操作前调用）
+pub fn ensure_vault_unlocked() -> Result<(), AppError> {
+    let config = load_config()?;
+    vault::ensure_unlocked(&config)
+}
+
+/// 锁定密钥库
+pub fn lock_vault() {
+    vault::lock_vault();
+}
+
+/// 停用密钥库，恢复明文保存
+pub fn disable_vault(passphrase: String) -> Result<(), AppError> {
+    update_config(|config| vault::disable_vault(config, &passphrase))
+}
+
+/// 切换 API Key 存储后端，并把已有密钥迁移到新后端
+///
+/// 先在配置锁外读取并写入各密钥的 API Key，事务内只提交存储引用的变化。
+/// 期间被其他进程删除或修改过的密钥不提交，写入新后端的 API Key 随后清理。
+pub fn set_secret_backend(backend: SecretBackend) -> Result<(), AppError> {
+    let config = load_config()?;
+    vault::ensure_unlocked(&config)?;
+
+    let target = secret_store::store_for_backend(backend);
+    let mut migrated: Vec<(Provider, Provider)> = Vec::new();
+    for provider in &config.providers {
+        let current = provider
+            .secret_ref
+            .as_ref()
+            .map(|r| r.backend)
+            .unwrap_or_default();
+        if current == backend {
+            continue;
+        }
+        let mut updated = provider.clone();
+        let stored =
+            resolve_api_key(provider).and_then(|api_key| target.put(&mut updated, &api_key));
+        if let Err(e) = stored {
+            delete_secrets(migrated.iter().map(|(_, new)| new));
+            return Err(e);
+        }
+        migrated.push((provider.clone(), updated));
+    }
+
+    let result = update_config(|config| {
+        let mut committed = Vec::new();
+        let mut skipped = Vec::new();
+        for (old, new) in &migrated {
+            match config.providers.iter_mut().find(|p| p.id == old.id) {
+                Some(provider)
+                    if provider.secret_ref == old.secret_ref && provider.api_key == old.api_key =>
+                {
+                    provider.api_key = new.api_key.clone();
+                    provider.encrypted_api_key = new.encrypted_api_key.clone();
+                    provider.secret_ref = new.secret_ref.clone();
+                    committed.push(old.clone());
+                }
+                // 其他进程已把该密钥迁移到同一位置时保留
+                Some(provider)
+                    if new.secret_ref.is_some() && provider.secret_ref == new.secret_ref => {}
+                _ => skipped.push(new.clone()),
+            }
+        }
+        config.secret_backend = backend;
+        Ok((committed, skipped))
+    });
+    let (committed, skipped) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            delete_secrets(migrated.iter().map(|(_, new)| new));
+            return Err(e);
+        }
+    };
+
+    // 新配置保存成功后再清理旧后端中的 API Key
+    delete_secrets(&committed);
+    delete_secrets(&skipped);
+
+    log::info!("API Key 存储后端已切换为: {:?}", backend);
     Ok(())
 }
+
+/// 删除存储后端中的 API Key（失败只记录日志）
+pub(crate) fn delete_secrets<'a>(providers: impl IntoIterator<Item = &'a Provider>) {
+    for provider in providers {
+        if let Err(e) = secret_store::store_for(provider).delete(provider) {
+            log::warn!("清理密钥 {} 的存储失败: {}", provider.name, e);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::balance::format_tokens;
+use crate::error::AppError;
+use crate::models::{Provider, QuotaAlert, QuotaAlertSettings};
+use crate::provider_manager::{load_config, update_config};
+
+/// 获取额度提醒设置
+pub fn get_settings() -> Result<QuotaAlertSettings, AppError> {
+    Ok(load_config()?.quota_alerts)
+}
+
+/// 保存额度提醒设置
+pub fn set_settings(mut settings: QuotaAlertSettings) -> Result<(), AppError> {
+    settings.thresholds = normalize_thresholds(settings.thresholds)?;
+    if !settings.hysteresis_percent.is_finite() || settings.hysteresis_percent < 0.0 {
+        return Err(AppError::invalid_input("回差必须是非负数"));
+    }
+
+    update_config(|config| {
+        config.quota_alerts = settings;
+        Ok(())
+    })?;
+    log::info!("已更新额度提醒设置");
+    Ok(())
+}
+
+/// 设置密钥单独的提醒阈值（传空使用全局设置）
+pub fn set_provider_thresholds(id: String, thresholds: Option<Vec<f64>>) -> Result<(), AppError> {
+    let thresholds = thresholds.map(normalize_thresholds).transpose()?;
+
+    let name = update_config(|config| {
+        let provider = config
+            .providers
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::not_found("密钥", id.clone()))?;
+        provider.alert_thresholds = thresholds;
+        Ok(provider.name.clone())
+    })?;
+    log::info!("已更新密钥 {} 的提醒阈值", name);
+    Ok(())
+}
+
+/// 校验阈值并按从小到大排序去重
+fn normalize_thresholds(mut thresholds: Vec<f64>) -> Result<Vec<f64>, AppError> {
+    if let Some(t) = thresholds.iter().find(|t| !t.is_finite() || **t <= 0.0) {
+        return Err(AppError::invalid_input(format!("无效的提醒阈值: {}", t)));
+    }
+    thresholds.sort_by(f64::total_cmp);
+    thresholds.dedup();
+    Ok(thresholds)
+}
+
+/// 根据密钥最新余额计算需要发出的提醒，并更新提醒状态
+fn evaluate(provider: &mut Provider, settings: &QuotaAlertSettings) -> Option<QuotaAlert> {
+    let balance = provider.balance.as_ref()?;
+    let used = balance.percent_used;
+    let thresholds = provider
+        .alert_thresholds
+        .as_ref()
+        .unwrap_or(&settings.thresholds);
+    let state = &mut provider.alert_state;
+
+    // 已用比例回落到阈值减去回差以下后（如额度重置），允许再次提醒
+    state
+        .fired_thresholds
+        .retain(|t| thresholds.contains(t) && used >= t - settings.hysteresis_percent);
+
+    let crossed: Vec<f64> = thresholds
+        .iter()
+        .copied()
+        .filter(|t| used >= *t && !state.fired_thresholds.contains(t))
+        .collect();
+    state.fired_thresholds.extend(&crossed);
+
+    let newly_exceeded = balance.exceeded && !state.exceeded_notified;
+    state.exceeded_notified = balance.exceeded;
+
+    let remaining = format_tokens(balance.remaining);
+    let (threshold, message) = if newly_exceeded && settings.notify_exceeded {
+        (
+            None,
+            format!("密钥 {} 额度已用完（已用 {:.1}%）", provider.name, used),
+        )
+    } else {
+        // 一次越过多个阈值时只提醒最高的一个
+        let threshold = crossed.last().copied()?;
+        (
+            Some(threshold),
+            format!(
+                "密钥 {} 已用 {:.1}%（超过 {}%），剩余 {}",
+                provider.name, used, threshold, remaining
+            ),
+        )
+    };
+
+    Some(QuotaAlert {
+        provider_id: provider.id.clone(),
+        provider_name: provider.name.clone(),
+        threshold,
+        exceeded: balance.exceeded,
+        percent_used: used,
+        remaining: balance.remaining,
+        message,
+    })
+}
+
+/// 检查所有密钥的最新余额，返回新越过阈值的提醒（提醒状态保存在配置中，重启后不会重复提醒）
+pub fn check_alerts() -> Result<Vec<QuotaAlert>, AppError> {
+    let alerts = update_config(|config| {
+        let settings = config.quota_alerts.clone();
+        if !settings.enabled {
+            return Ok(Vec::new());
+        }
+        Ok(config
+            .providers
+            .iter_mut()
+            .filter_map(|p| evaluate(p, &settings))
+            .collect::<Vec<_>>())
+    })?;
+
+    for alert in &alerts {
+        log::info!("额度提醒: {}", alert.message);
+    }
+    Ok(alerts)
+}
@@ -0,0 +1,158 @@
+use crate::config::lock_app_config;
+use crate::error::AppError;
+use crate::models::{
+    AppConfig, BalanceInfo, Provider, ProviderStatus, RotationOrder, RotationPolicy,
+};
+use crate::provider_manager::{load_config, switch_provider, update_config};
+use serde::Serialize;
+
+/// 自动轮换结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationOutcome {
+    /// 被替换的密钥 ID
+    pub from_id: String,
+    /// 切换后的密钥
+    pub provider: Provider,
+    /// 轮换原因
+    pub reason: String,
+}
+
+/// 获取自动轮换策略
+pub fn get_policy() -> Result<RotationPolicy, AppError> {
+    Ok(load_config()?.rotation_policy)
+}
+
+/// 保存自动轮换策略
+pub fn set_policy(policy: RotationPolicy) -> Result<(), AppError> {
+    if !(0.0..=100.0).contains(&policy.threshold_percent) {
+        return Err(AppError::invalid_input("轮换阈值必须在 0 到 100 之间"));
+    }
+
+    update_config(|config| {
+        config.rotation_policy = policy;
+        Ok(())
+    })?;
+
+    log::info!("已更新自动轮换策略");
+    Ok(())
+}
+
+/// 判断余额是否已达到轮换条件，返回原因
+fn rotation_reason(balance: &BalanceInfo, threshold_percent: f64) -> Option<String> {
+    if balance.exceeded {
+        return Some("配额已用尽".to_string());
+    }
+    if balance.percent_used >= threshold_percent {
+        return Some(format!(
+            "已用 {:.1}%，超过阈值 {:.0}%",
+            balance.percent_used, threshold_percent
+        ));
+    }
+    None
+}
+
+/// 密钥是否已被拒绝（无效或已吊销）
+fn is_rejected(provider: &Provider) -> bool {
+    matches!(
+        provider.status,
+        ProviderStatus::Invalid | ProviderStatus::Revoked
+    )
+}
+
+/// 候选密钥必须未被排除、未过期、未被拒绝、余额已知且未达到轮换条件
+fn is_candidate(provider: &Provider, policy: &RotationPolicy, active_id: &str) -> bool {
+    provider.id != active_id
+        && !provider.expired
+        && !is_rejected(provider)
+        && !policy.excluded_ids.contains(&provider.id)
+        && provider
+            .balance
+            .as_ref()
+            .is_some_and(|b| rotation_reason(b, policy.threshold_percent).is_none())
+}
+
+/// 按策略从候选中选出最佳密钥
+fn pick_candidate<'a>(config: &'a AppConfig, active_id: &str) -> Option<&'a Provider> {
+    let policy = &config.rotation_policy;
+    let candidates = config
+        .providers
+        .iter()
+        .filter(|p| is_candidate(p, policy, active_id));
+
+    let remaining = |p: &Provider| p.balance.as_ref().map(|b| b.remaining).unwrap_or(0);
+
+    match policy.order {
+        RotationOrder::MostRemaining => candidates.max_by_key(|p| remaining(p)),
+        RotationOrder::SoonestExpiry => {
+            // 没有到期时间的排在最后，到期时间相同时剩余额度多的优先
+            candidates.min_by(|a, b| {
+                let expiry = |p: &Provider| {
+                    p.balance
+                        .as_ref()
+                        .and_then(|b| b.expiry_date.as_deref())
+                        .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+                };
+                match (expiry(a), expiry(b)) {
+                    (Some(x), Some(y)) => x.cmp(&y).then(remaining(b).cmp(&remaining(a))),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => remaining(b).cmp(&remaining(a)),
+                }
+            })
+        }
+        RotationOrder::Manual => {
+            let candidates: Vec<&Provider> = candidates.collect();
+            policy
+                .manual_order
+                .iter()
+                .find_map(|id| candidates.iter().find(|p| &p.id == id).copied())
+        }
+    }
+}
+
+/// 检查当前激活密钥是否需要轮换，返回（原因，候选密钥 ID）
+///
+/// 当前密钥已过期时，即使未启用自动轮换也会切换（可在到期提醒设置中关闭）。
+pub fn evaluate(config: &AppConfig) -> Option<(String, String)> {
+    let policy = &config.rotation_policy;
+    let active_id = config.active_provider_id.as_deref()?;
+    let active = config.providers.iter().find(|p| p.id == active_id)?;
+    let reason = if active.expired && config.expiry.switch_when_expired {
+        "密钥已过期".to_string()
+    } else if policy.enabled && is_rejected(active) {
+        "密钥无效或已吊销".to_string()
+    } else if policy.enabled {
+        rotation_reason(active.balance.as_ref()?, policy.threshold_percent)?
+    } else {
+        return None;
+    };
+
+    match pick_candidate(config, active_id) {
+        Some(candidate) => Some((reason, candidate.id.clone())),
+        None => {
+            log::warn!("密钥 {} {}，但没有可轮换的候选密钥", active.name, reason);
+            None
+        }
+    }
+}
+
+/// 按策略在需要时自动切换密钥
+pub fn rotate_if_needed() -> Result<Option<RotationOutcome>, AppError> {
+    // 判断和切换在同一把配置锁内完成，避免期间配置被其他进程修改
+    let _lock = lock_app_config()?;
+    let config = load_config()?;
+    let Some((reason, candidate_id)) = evaluate(&config) else {
+        return Ok(None);
+    };
+
+    let from_id = config.active_provider_id.clone().unwrap_or_default();
+    let provider = switch_provider(candidate_id)?;
+    log::info!("自动轮换到密钥 {}: {}", provider.name, reason);
+
+    Ok(Some(RotationOutcome {
+        from_id,
+        provider,
+        reason,
+    }))
+}
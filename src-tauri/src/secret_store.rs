@@ -0,0 +1,255 @@
+use crate::error::AppError;
+use crate::models::{Provider, SecretBackend, SecretRef};
+use crate::vault;
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::sync::{Mutex, OnceLock};
+
+/// 系统钥匙串中使用的服务名
+const KEYRING_SERVICE: &str = "factory-ai-droid-switch";
+
+/// API Key 存储接口
+///
+/// Provider 只保存引用，具体的 API Key 由实现决定保存位置。
+pub trait SecretStore: Send + Sync {
+    /// 读取密钥对应的 API Key
+    fn get(&self, provider: &Provider) -> Result<String, AppError>;
+
+    /// 保存 API Key，并在 provider 上记录引用
+    fn put(&self, provider: &mut Provider, api_key: &str) -> Result<(), AppError>;
+
+    /// 删除密钥对应的 API Key
+    fn delete(&self, provider: &Provider) -> Result<(), AppError>;
+}
+
+/// 内联存储：API Key 保存在应用 config.json 中（默认行为）
+pub struct InlineStore;
+
+impl SecretStore for InlineStore {
+    fn get(&self, provider: &Provider) -> Result<String, AppError> {
+        if provider.api_key.is_empty() && provider.encrypted_api_key.is_some() {
+            return Err(vault::locked_error());
+        }
+        Ok(provider.api_key.clone())
+    }
+
+    fn put(&self, provider: &mut Provider, api_key: &str) -> Result<(), AppError> {
+        provider.api_key = api_key.to_string();
+        provider.encrypted_api_key = None;
+        provider.secret_ref = None;
+        Ok(())
+    }
+
+    fn delete(&self, _provider: &Provider) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+/// 系统钥匙串存储（Linux Secret Service / macOS Keychain / Windows 凭据管理器）
+pub struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(account: &str) -> Result<keyring::Entry, AppError> {
+        keyring::Entry::new(KEYRING_SERVICE, account)
+            .map_err(|e| AppError::other(format!("打开系统钥匙串失败: {}", e)))
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self, provider: &Provider) -> Result<String, AppError> {
+        let account = account_of(provider)?;
+        Self::entry(account)?.get_password().map_err(|e| {
+            AppError::other(format!(
+                "从系统钥匙串读取密钥 {} 失败: {}",
+                provider.name, e
+            ))
+        })
+    }
+
+    fn put(&self, provider: &mut Provider, api_key: &str) -> Result<(), AppError> {
+        let account = provider.id.clone();
+        Self::entry(&account)?
+            .set_password(api_key)
+            .map_err(|e| AppError::other(format!("写入系统钥匙串失败: {}", e)))?;
+
+        provider.api_key.clear();
+        provider.encrypted_api_key = None;
+        provider.secret_ref = Some(SecretRef {
+            backend: SecretBackend::Keyring,
+            account,
+        });
+        Ok(())
+    }
+
+    fn delete(&self, provider: &Provider) -> Result<(), AppError> {
+        let account = account_of(provider)?;
+        match Self::entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::other(format!("从系统钥匙串删除密钥失败: {}", e))),
+        }
+    }
+}
+
+/// 内存存储：不持久化，仅在测试中可用
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    secrets: Mutex<HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl SecretStore for MemoryStore {
+    fn get(&self, provider: &Provider) -> Result<String, AppError> {
+        let account = account_of(provider)?;
+        self.secrets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(account)
+            .cloned()
+            .ok_or_else(|| AppError::not_found("内存存储中的密钥", provider.name.clone()))
+    }
+
+    fn put(&self, provider: &mut Provider, api_key: &str) -> Result<(), AppError> {
+        let account = provider.id.clone();
+        self.secrets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(account.clone(), api_key.to_string());
+
+        provider.api_key.clear();
+        provider.encrypted_api_key = None;
+        provider.secret_ref = Some(SecretRef {
+            backend: SecretBackend::Memory,
+            account,
+        });
+        Ok(())
+    }
+
+    fn delete(&self, provider: &Provider) -> Result<(), AppError> {
+        let account = account_of(provider)?;
+        self.secrets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(account);
+        Ok(())
+    }
+}
+
+fn account_of(provider: &Provider) -> Result<&str, AppError> {
+    provider
+        .secret_ref
+        .as_ref()
+        .map(|r| r.account.as_str())
+        .ok_or_else(|| AppError::other(format!("密钥 {} 没有外部存储引用", provider.name)))
+}
+
+/// 获取指定后端的存储实现
+pub fn store_for_backend(backend: SecretBackend) -> &'static dyn SecretStore {
+    static INLINE: InlineStore = InlineStore;
+    static KEYRING: KeyringStore = KeyringStore;
+    #[cfg(test)]
+    static MEMORY: OnceLock<MemoryStore> = OnceLock::new();
+
+    match backend {
+        SecretBackend::Inline => &INLINE,
+        SecretBackend::Keyring => &KEYRING,
+        #[cfg(test)]
+        SecretBackend::Memory => MEMORY.get_or_init(MemoryStore::default),
+    }
+}
+
+/// 获取密钥当前所在的存储实现
+pub fn store_for(provider: &Provider) -> &'static dyn SecretStore {
+    let backend = provider
+        .secret_ref
+        .as_ref()
+        .map(|r| r.backend)
+        .unwrap_or_default();
+    store_for_backend(backend)
+}
+
+/// 解析密钥的 API Key
+pub fn resolve_api_key(provider: &Provider) -> Result<String, AppError> {
+    store_for(provider).get(provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn provider(id: &str) -> Provider {
+        serde_json::from_value(json!({ "id": id, "name": format!("key-{}", id) })).unwrap()
+    }
+
+    #[test]
+    fn memory_store_round_trip() {
+        let store = MemoryStore::default();
+        let mut p = provider("p1");
+        p.api_key = "fk-old".to_string();
+
+        store.put(&mut p, "fk-secret").unwrap();
+        assert!(p.api_key.is_empty());
+        assert_eq!(
+            p.secret_ref,
+            Some(SecretRef {
+                backend: SecretBackend::Memory,
+                account: "p1".to_string(),
+            })
+        );
+        assert_eq!(store.get(&p).unwrap(), "fk-secret");
+
+        store.put(&mut p, "fk-rotated").unwrap();
+        assert_eq!(store.get(&p).unwrap(), "fk-rotated");
+
+        store.delete(&p).unwrap();
+        assert!(store.get(&p).is_err());
+        // 重复删除不报错
+        store.delete(&p).unwrap();
+    }
+
+    #[test]
+    fn memory_store_requires_secret_ref() {
+        let store = MemoryStore::default();
+        assert!(store.get(&provider("p2")).is_err());
+    }
+
+    #[test]
+    fn inline_store_round_trip() {
+        let mut p = provider("p3");
+        p.secret_ref = Some(SecretRef {
+            backend: SecretBackend::Memory,
+            account: "p3".to_string(),
+        });
+        InlineStore.put(&mut p, "fk-inline").unwrap();
+        assert_eq!(p.api_key, "fk-inline");
+        assert!(p.secret_ref.is_none());
+        assert_eq!(InlineStore.get(&p).unwrap(), "fk-inline");
+    }
+
+    #[test]
+    fn inline_store_reports_locked_vault() {
+        let mut p = provider("p4");
+        p.encrypted_api_key = Some("enc:v1:nonce:ciphertext".to_string());
+        assert!(InlineStore.get(&p).is_err());
+    }
+
+    #[test]
+    fn resolves_through_the_referenced_backend() {
+        let mut p = provider("p5");
+        store_for_backend(SecretBackend::Memory)
+            .put(&mut p, "fk-shared")
+            .unwrap();
+        assert_eq!(resolve_api_key(&p).unwrap(), "fk-shared");
+
+        // 移回内联存储后不再依赖内存存储
+        let previous = p.clone();
+        store_for_backend(SecretBackend::Inline)
+            .put(&mut p, "fk-shared")
+            .unwrap();
+        store_for(&previous).delete(&previous).unwrap();
+        assert_eq!(resolve_api_key(&p).unwrap(), "fk-shared");
+        assert!(resolve_api_key(&previous).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::error::AppError;
+use crate::models::{AppConfig, VaultConfig, VaultStatus};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Utc;
+use std::sync::Mutex;
+
+/// 当前密钥库格式版本
+const VAULT_VERSION: u32 = 1;
+
+/// 加密后 API Key 的前缀
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// 用于校验口令的固定明文
+const VERIFIER_PLAINTEXT: &[u8] = b"factory-ai-droid-switch-vault";
+
+/// 解锁后保存在内存中的派生密钥（进程退出或锁定后清除）
+static SESSION_KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+/// 密钥库锁定时返回的错误信息
+pub const VAULT_LOCKED_ERROR: &str = "密钥库已锁定，请先解锁";
+
+/// 密钥库锁定错误
+pub fn locked_error() -> AppError {
+    AppError::Locked(VAULT_LOCKED_ERROR.to_string())
+}
+
+/// 使用 Argon2id 从口令派生 256 位密钥
+fn derive_key(passphrase: &str, vault: &VaultConfig) -> Result<[u8; 32], AppError> {
+    let salt = BASE64
+        .decode(&vault.salt)
+        .map_err(|e| AppError::parse("解析密钥库盐值失败", e))?;
+    let params = Params::new(vault.m_cost, vault.t_cost, vault.p_cost, Some(32))
+        .map_err(|e| AppError::other(format!("密钥库参数无效: {}", e)))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| AppError::other(format!("派生密钥失败: {}", e)))?;
+    Ok(key)
+}
+
+/// 使用 AES-256-GCM 加密，输出 enc:v1:<nonce>:<ciphertext>
+fn encrypt_with(key: &[u8; 32], plaintext: &[u8]) -> Result<String, AppError> {
+    let cipher = Aes256Gcm::new(key.into());
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+        .map_err(|_| AppError::other("加密失败"))?;
+
+    Ok(format!(
+        "{}{}:{}",
+        ENCRYPTED_PREFIX,
+        BASE64.encode(nonce_bytes),
+        BASE64.encode(ciphertext)
+    ))
+}
+
+/// 解密 encrypt_with 的输出
+fn decrypt_with(key: &[u8; 32], encoded: &str) -> Result<Vec<u8>, AppError> {
+    let body = encoded
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| AppError::Parse("不支持的加密格式".to_string()))?;
+    let (nonce_b64, ciphertext_b64) = body
+        .split_once(':')
+        .ok_or_else(|| AppError::Parse("加密数据格式无效".to_string()))?;
+
+    let nonce_bytes = BASE64
+        .decode(nonce_b64)
+        .map_err(|e| AppError::parse("解析 nonce 失败", e))?;
+    if nonce_bytes.len() != 12 {
+        return Err(AppError::Parse("加密数据格式无效".to_string()));
+    }
+    let ciphertext = BASE64
+        .decode(ciphertext_b64)
+        .map_err(|e| AppError::parse("解析密文失败", e))?;
+
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .decrypt(Nonce::from_slice(&nonce_bytes), ciphertext.as_ref())
+        .map_err(|_| AppError::Locked("解密失败：口令错误或数据已损坏".to_string()))
+}
+
+/// 校验口令并返回派生密钥
+fn verify_passphrase(passphrase: &str, vault: &VaultConfig) -> Result<[u8; 32], AppError> {
+    let key = derive_key(passphrase, vault)?;
+    match decrypt_with(&key, &vault.verifier) {
+        Ok(plain) if plain == VERIFIER_PLAINTEXT => Ok(key),
+        _ => Err(AppError::Locked("口令错误".to_string())),
+    }
+}
+
+fn session_key() -> Option<[u8; 32]> {
+    *SESSION_KEY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn set_session_key(key: Option<[u8; 32]>) {
+    let mut guard = SESSION_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(old) = guard.as_mut() {
+        old.fill(0);
+    }
+    *guard = key;
+}
+
+/// 密钥库当前是否已解锁
+pub fn is_unlocked() -> bool {
+    session_key().is_some()
+}
+
+/// 获取密钥库状态
+pub fn get_status(config: &AppConfig) -> VaultStatus {
+    VaultStatus {
+        enabled: config.vault.is_some(),
+        unlocked: is_unlocked(),
+    }
+}
+
+/// 启用了密钥库但尚未解锁时返回错误
+pub fn ensure_unlocked(config: &AppConfig) -> Result<(), AppError> {
+    if config.vault.is_some() && !is_unlocked() {
+        return Err(locked_error());
+    }
+    Ok(())
+}
+
+/// 加载配置后调用：已解锁时将 encrypted_api_key 解密到 api_key
+///
+/// 单个密文损坏时不影响其他密钥：在该密钥上记录错误，保留密文和空的 api_key。
+pub fn decrypt_providers(config: &mut AppConfig) -> Result<(), AppError> {
+    if config.vault.is_none() {
+        return Ok(());
+    }
+    let Some(key) = session_key() else {
+        return Ok(());
+    };
+
+    for provider in &mut config.providers {
+        let Some(encrypted) = &provider.encrypted_api_key else {
+            continue;
+        };
+        let decrypted = decrypt_with(&key, encrypted).and_then(|plain| {
+            String::from_utf8(plain)
+                .map_err(|_| AppError::Parse(format!("密钥 {} 解密结果无效", provider.name)))
+        });
+        match decrypted {
+            Ok(api_key) => provider.api_key = api_key,
+            Err(e) => {
+                log::warn!("解密密钥 {} 失败: {}", provider.name, e);
+                provider.last_error = Some(e.to_string());
+                provider.last_error_at = Some(Utc::now().to_rfc3339());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 保存配置前调用：返回适合写入磁盘的配置副本
+///
+/// 启用密钥库时明文 api_key 会被加密到 encrypted_api_key 并清空；
+/// 未解锁时只能保留已有的密文，出现新的明文则拒绝保存。
+pub fn encrypt_providers(config: &AppConfig) -> Result<AppConfig, AppError> {
+    let mut on_disk = config.clone();
+    if on_disk.vault.is_none() {
+        for provider in &mut on_disk.providers {
+            provider.encrypted_api_key = None;
+        }
+        return Ok(on_disk);
+    }
+
+    let key = session_key();
+    for provider in &mut on_disk.providers {
+        if provider.api_key.is_empty() {
+            continue;
+        }
+        let key = key.ok_or_else(locked_error)?;
+        provider.encrypted_api_key = Some(encrypt_with(&key, provider.api_key.as_bytes())?);
+        provider.api_key.clear();
+    }
+    Ok(on_disk)
+}
+
+/// 启用密钥库：用口令加密现有的明文 API Key（明文配置的迁移入口）
+pub fn enable_vault(config: &mut AppConfig, passphrase: &str) -> Result<(), AppError> {
+    if config.vault.is_some() {
+        return Err(AppError::invalid_input("密钥库已启用"));
+    }
+    if passphrase.is_empty() {
+        return Err(AppError::invalid_input("口令不能为空"));
+    }
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut vault = VaultConfig {
+        version: VAULT_VERSION,
+        salt: BASE64.encode(salt),
+        verifier: String::new(),
+        m_cost: Params::DEFAULT_M_COST,
+        t_cost: Params::DEFAULT_T_COST,
+        p_cost: Params::DEFAULT_P_COST,
+    };
+    let key = derive_key(passphrase, &vault)?;
+    vault.verifier = encrypt_with(&key, VERIFIER_PLAINTEXT)?;
+
+    config.vault = Some(vault);
+    set_session_key(Some(key));
+    log::info!("已启用密钥库");
+    Ok(())
+}
+
+/// 解锁密钥库
+pub fn unlock_vault(config: &AppConfig, passphrase: &str) -> Result<(), AppError> {
+    let vault = config
+        .vault
+        .as_ref()
+        .ok_or_else(|| AppError::invalid_input("密钥库未启用"))?;
+    let key = verify_passphrase(passphrase, vault)?;
+    set_session_key(Some(key));
+    log::info!("密钥库已解锁");
+    Ok(())
+}
+
+/// 锁定密钥库，清除内存中的派生密钥
+pub fn lock_vault() {
+    set_session_key(None);
+    log::info!("密钥库已锁定");
+}
+
+/// 停用密钥库：校验口令后恢复为明文保存
+///
+/// 调用方需在解密后的配置上调用，并随后保存配置。
+pub fn disable_vault(config: &mut AppConfig, passphrase: &str) -> Result<(), AppError> {
+    let vault = config
+        .vault
+        .as_ref()
+        .ok_or_else(|| AppError::invalid_input("密钥库未启用"))?;
+    let key = verify_passphrase(passphrase, vault)?;
+
+    for provider in &mut config.providers {
+        if let Some(encrypted) = provider.encrypted_api_key.take() {
+            let plain = decrypt_with(&key, &encrypted)?;
+            provider.api_key = String::from_utf8(plain)
+                .map_err(|_| AppError::Parse(format!("密钥 {} 解密结果无效", provider.name)))?;
+        }
+    }
+    config.vault = None;
+    set_session_key(None);
+    log::info!("已停用密钥库");
+    Ok(())
+}
@@ -0,0 +1,466 @@
+//! Factory.ai 客户端集成测试：使用本地 mock 服务器，无需真实 API Key
+
+use droid_switch_lib::balance;
+use droid_switch_lib::factory_client::{
+    parse_chat_usage, parse_retry_after, resolve_base_url, FactoryClient, HttpFactoryClient,
+    DEFAULT_BASE_URL,
+};
+use droid_switch_lib::models::{
+    AppConfig, BalanceCheckSettings, ConnectivityStage, NetworkSettings,
+};
+use droid_switch_lib::network;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const USAGE_PATH: &str = "/api/organization/members/chat-usage";
+
+fn usage_body(used: u64, allowance: u64, end_date: Option<i64>) -> serde_json::Value {
+    json!({
+        "usage": {
+            "standard": {
+                "userTokens": used,
+                "totalAllowance": allowance,
+                "orgOverageUsed": 0,
+                "usedRatio": used as f64 / allowance as f64,
+            },
+            "endDate": end_date,
+        }
+    })
+}
+
+async fn client_for(server: &MockServer) -> HttpFactoryClient {
+    HttpFactoryClient::new(&server.uri()).unwrap()
+}
+
+/// 返回一个没有监听的本地地址
+fn unused_addr() -> String {
+    // 绑定后立即释放端口
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    format!("http://127.0.0.1:{}", port)
+}
+
+fn proxy_settings(proxy: &str) -> NetworkSettings {
+    NetworkSettings {
+        proxy: Some(proxy.to_string()),
+        ..NetworkSettings::default()
+    }
+}
+
+/// 重试间隔很短，避免拖慢测试
+fn fast_retry(max_retries: u32) -> BalanceCheckSettings {
+    BalanceCheckSettings {
+        concurrency: 4,
+        max_retries,
+        retry_base_delay_ms: 10,
+    }
+}
+
+#[tokio::test]
+async fn parses_usage_from_mock_server() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .and(header("Authorization", "Bearer fk-test"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(
+            5_000_000,
+            20_000_000,
+            Some(1_767_225_600_000),
+        )))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let info = client_for(&server)
+        .await
+        .chat_usage("fk-test")
+        .await
+        .unwrap();
+
+    assert_eq!(info.used, 5_000_000);
+    assert_eq!(info.allowance, 20_000_000);
+    assert_eq!(info.remaining, 15_000_000);
+    assert!((info.percent_used - 25.0).abs() < 1e-9);
+    assert!(!info.exceeded);
+    assert_eq!(
+        info.expiry_date.as_deref(),
+        Some("2026-01-01T00:00:00+00:00")
+    );
+}
+
+#[tokio::test]
+async fn reports_exceeded_quota() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(25, 20, None)))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let info = balance::check_balance_with(&client, "fk-test")
+        .await
+        .unwrap();
+
+    assert_eq!(info.remaining, 0);
+    assert!(info.exceeded);
+    assert_eq!(info.expiry_date, None);
+}
+
+#[tokio::test]
+async fn returns_http_status_on_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(401).set_body_string("invalid api key"))
+        .mount(&server)
+        .await;
+
+    let err = client_for(&server)
+        .await
+        .chat_usage("fk-revoked")
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.contains("401"), "{}", err);
+    assert!(err.contains("invalid api key"), "{}", err);
+}
+
+#[tokio::test]
+async fn rejects_malformed_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "usage": {} })))
+        .mount(&server)
+        .await;
+
+    let err = client_for(&server)
+        .await
+        .chat_usage("fk-test")
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.contains("解析响应失败"), "{}", err);
+}
+
+#[tokio::test]
+async fn reports_network_errors() {
+    let err = HttpFactoryClient::new(&unused_addr())
+        .unwrap()
+        .chat_usage("fk-test")
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.contains("请求失败"), "{}", err);
+}
+
+#[tokio::test]
+async fn batch_reports_errors_per_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .and(header("Authorization", "Bearer fk-good"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(1, 10, None)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .and(header("Authorization", "Bearer fk-bad"))
+        .respond_with(ResponseTemplate::new(403))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let results = balance::batch_check_balances_with(
+        &client,
+        vec!["fk-good".to_string(), "fk-bad".to_string()],
+        &fast_retry(3),
+    )
+    .await;
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results["fk-good"].balance.as_ref().unwrap().remaining, 9);
+    assert!(results["fk-good"].error.is_none());
+    assert!(results["fk-bad"].balance.is_none());
+    let err = results["fk-bad"].error.as_ref().unwrap();
+    assert_eq!(err.code(), "Unauthorized");
+    assert!(err.to_string().contains("403"), "{}", err);
+}
+
+#[tokio::test]
+async fn serializes_errors_with_code_and_context() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(401).set_body_string("invalid api key"))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let err = balance::check_balance_with_retry(&client, "fk-revoked", &fast_retry(3))
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        json!({
+            "code": "Unauthorized",
+            "message": "HTTP 401: invalid api key",
+            "context": { "status": 401 },
+        })
+    );
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(1, 10, None)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let info = balance::check_balance_with_retry(&client, "fk-test", &fast_retry(3))
+        .await
+        .unwrap();
+
+    assert_eq!(info.remaining, 9);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let err = balance::check_balance_with_retry(&client, "fk-test", &fast_retry(2))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.code(), "Http");
+    assert!(err.to_string().contains("500"), "{}", err);
+}
+
+#[tokio::test]
+async fn honors_retry_after_on_429() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(1, 10, None)))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server).await;
+    let started = Instant::now();
+    balance::check_balance_with_retry(&client, "fk-test", &fast_retry(1))
+        .await
+        .unwrap();
+
+    // 退避间隔只有 10ms，等待 1s 说明使用了 Retry-After
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn parses_retry_after_header() {
+    assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon"), None);
+}
+
+#[test]
+fn trims_trailing_slash_from_base_url() {
+    let client = HttpFactoryClient::new("http://localhost:8080/").unwrap();
+    assert_eq!(client.base_url(), "http://localhost:8080");
+}
+
+#[test]
+fn base_url_falls_back_to_default() {
+    // 环境变量会覆盖配置，设置时跳过
+    if std::env::var_os("FACTORY_API_BASE_URL").is_some() {
+        return;
+    }
+
+    let mut config = AppConfig::default();
+    assert_eq!(resolve_base_url(&config), DEFAULT_BASE_URL);
+
+    config.api_base_url = Some("https://staging.example.com".to_string());
+    assert_eq!(resolve_base_url(&config), "https://staging.example.com");
+}
+
+#[test]
+fn parse_ignores_unknown_fields() {
+    let body = json!({
+        "usage": {
+            "standard": {
+                "userTokens": 10,
+                "totalAllowance": 100,
+                "orgOverageUsed": 2,
+                "usedRatio": 0.1,
+                "somethingNew": true,
+            },
+            "endDate": null,
+            "premium": {},
+        },
+        "extra": 1,
+    });
+
+    let info = parse_chat_usage(&body.to_string()).unwrap();
+    assert_eq!(info.remaining, 90);
+    assert_eq!(info.overage, 2);
+    // 空的 premium 不是有效档位
+    assert_eq!(info.tiers.len(), 1);
+    assert_eq!(info.raw.unwrap()["extra"], 1);
+}
+
+#[test]
+fn parses_all_usage_tiers_and_period() {
+    let body = json!({
+        "usage": {
+            "startDate": 1_764_547_200_000i64,
+            "endDate": 1_767_225_600_000i64,
+            "standard": {
+                "userTokens": 10,
+                "totalAllowance": 100,
+                "orgOverageUsed": 0,
+                "usedRatio": 0.1,
+                "orgTotalTokensUsed": 500,
+            },
+            "premium": {
+                "userTokens": 30,
+                "totalAllowance": 20,
+                "orgOverageUsed": 10,
+                "usedRatio": 1.5,
+                "orgOverageLimit": 1000,
+            },
+        },
+        "organization": { "name": "acme" },
+    });
+
+    let info = parse_chat_usage(&body.to_string()).unwrap();
+
+    assert_eq!(info.remaining, 90);
+    assert_eq!(
+        info.period_start.as_deref(),
+        Some("2025-12-01T00:00:00+00:00")
+    );
+    assert_eq!(
+        info.expiry_date.as_deref(),
+        Some("2026-01-01T00:00:00+00:00")
+    );
+
+    let standard = &info.tiers[0];
+    assert_eq!(standard.name, "standard");
+    assert_eq!(standard.org_used, Some(500));
+
+    let premium = info.tiers.iter().find(|t| t.name == "premium").unwrap();
+    assert_eq!(premium.remaining, 0);
+    assert_eq!(premium.overage, 10);
+    assert_eq!(premium.overage_limit, Some(1000));
+
+    // 原始响应只保留在内存中，不随余额写入配置或发送给前端
+    let serialized = serde_json::to_value(&info).unwrap();
+    assert!(serialized.get("raw").is_none());
+    assert_eq!(info.raw.unwrap()["organization"]["name"], "acme");
+}
+
+#[tokio::test]
+async fn routes_requests_through_proxy() {
+    // mock 服务器充当 HTTP 代理：请求地址无法解析，只能经代理转发
+    let proxy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(USAGE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(1, 10, None)))
+        .expect(1)
+        .mount(&proxy)
+        .await;
+
+    let client =
+        HttpFactoryClient::with_settings("http://factory.invalid", &proxy_settings(&proxy.uri()))
+            .unwrap();
+    let info = client.chat_usage("fk-test").await.unwrap();
+
+    assert_eq!(info.remaining, 9);
+}
+
+#[tokio::test]
+async fn connectivity_succeeds_on_any_http_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let report = network::test_connectivity_with(&server.uri(), &NetworkSettings::default()).await;
+
+    assert!(report.ok);
+    assert_eq!(report.status, Some(401));
+    assert_eq!(report.failed_stage, None);
+}
+
+#[tokio::test]
+async fn connectivity_reports_failed_stage() {
+    let direct = network::test_connectivity_with(&unused_addr(), &NetworkSettings::default()).await;
+    assert!(!direct.ok);
+    assert_eq!(direct.failed_stage, Some(ConnectivityStage::Connect));
+    assert!(direct.error.is_some());
+
+    let proxied =
+        network::test_connectivity_with("http://factory.invalid", &proxy_settings(&unused_addr()))
+            .await;
+    assert_eq!(proxied.failed_stage, Some(ConnectivityStage::Proxy));
+
+    let settings = NetworkSettings {
+        ca_bundle_path: Some("/nonexistent/ca.pem".to_string()),
+        ..NetworkSettings::default()
+    };
+    let invalid = network::test_connectivity_with("http://factory.invalid", &settings).await;
+    assert_eq!(invalid.failed_stage, Some(ConnectivityStage::Config));
+}
+
+#[test]
+fn rejects_invalid_network_settings() {
+    let err = network::build_client(&proxy_settings("not a url")).unwrap_err();
+    assert_eq!(err.code(), "InvalidInput");
+
+    let settings = NetworkSettings {
+        timeout_secs: 0,
+        ..NetworkSettings::default()
+    };
+    assert_eq!(
+        network::build_client(&settings).unwrap_err().code(),
+        "InvalidInput"
+    );
+}
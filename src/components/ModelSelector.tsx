@@ -342,6 +342,9 @@ export const ModelSelector: React.FC<ModelSelectorProps> = ({ onModelChange }) =
                            {model.isBuiltin && (
                               <span className="bg-blue-100 dark:bg-blue-900/30 text-blue-600 dark:text-blue-400 px-1.5 py-0.5 rounded text-[10px] uppercase tracking-wide font-medium">内置</span>
                            )}
+                           {model.costMultiplier !== undefined && (
+                              <span className="bg-amber-100 dark:bg-amber-900/30 text-amber-600 dark:text-amber-400 px-1.5 py-0.5 rounded text-[10px] tracking-wide font-medium">{model.costMultiplier}x</span>
+                           )}
                            {model.reasoningLevel !== 'off' && (
                               <span className="bg-purple-100 dark:bg-purple-900/30 text-purple-600 dark:text-purple-400 px-1.5 py-0.5 rounded text-[10px] uppercase tracking-wide font-medium">
                                 推理: {REASONING_LEVEL_LABELS[model.reasoningLevel]}
@@ -2,8 +2,9 @@
  * 自定义 Hook: 管理余额查询
  */
 
-import { useState, useCallback } from 'react';
-import { BalanceInfo } from '../types/api';
+import { useState, useCallback, useEffect } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { BalanceInfo, BalanceUpdatedEvent } from '../types/api';
 import DroidAPI from '../types/api';
 
 export function useBalance() {
@@ -12,6 +13,39 @@ export function useBalance() {
   const [checkingIds, setCheckingIds] = useState<Set<string>>(new Set());
   const [errors, setErrors] = useState<Record<string, string>>({});
 
+  // 后台定时刷新和批量刷新时，每个密钥查询完成都会发送 balance-updated 事件
+  useEffect(() => {
+    const unlisten = listen<BalanceUpdatedEvent>('balance-updated', ({ payload }) => {
+      const { providerId, balance, error } = payload;
+      if (error) {
+        setErrors(prev => ({
+          ...prev,
+          [providerId]: error.message,
+        }));
+        return;
+      }
+      if (balance) {
+        setBalances(prev => ({
+          ...prev,
+          [providerId]: balance,
+        }));
+        setLastChecked(prev => ({
+          ...prev,
+          [providerId]: Date.now(),
+        }));
+        setErrors(prev => {
+          const next = { ...prev };
+          delete next[providerId];
+          return next;
+        });
+      }
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
   // 查询单个余额
   const checkBalance = useCallback(async (id: string, apiKey: string) => {
     setCheckingIds(prev => new Set(prev).add(id));
@@ -69,9 +103,14 @@ export function useBalance() {
       const results = await DroidAPI.batchCheckBalances(apiKeys);
 
       // 更新余额
-      Object.entries(results).forEach(([apiKey, balance]) => {
+      Object.entries(results).forEach(([apiKey, { balance, error }]) => {
         const id = idMap[apiKey];
-        if (id) {
+        if (id && error) {
+          setErrors(prev => ({
+            ...prev,
+            [id]: error.message,
+          }));
+        } else if (id && balance) {
           setBalances(prev => ({
             ...prev,
             [id]: balance,
@@ -152,11 +191,16 @@ export function useBalance() {
   // 刷新所有余额
   const refreshAllBalances = useCallback(async () => {
     try {
-      const providers = await DroidAPI.refreshAllBalances();
+      const { providers, errors: failed } = await DroidAPI.refreshAllBalances();
 
       // 更新所有余额
       providers.forEach(provider => {
-        if (provider.balance) {
+        if (failed[provider.id]) {
+          setErrors(prev => ({
+            ...prev,
+            [provider.id]: failed[provider.id].message,
+          }));
+        } else if (provider.balance) {
           setBalances(prev => ({
             ...prev,
             [provider.id]: provider.balance!,
@@ -165,6 +209,11 @@ export function useBalance() {
             ...prev,
             [provider.id]: Date.now(),
           }));
+          setErrors(prev => {
+            const next = { ...prev };
+            delete next[provider.id];
+            return next;
+          });
         }
       });
 
@@ -18,12 +18,28 @@ export interface Provider {
   apiKey: string;
   /** 余额信息 (可选) */
   balance?: BalanceInfo;
+  /** 最近一次成功查询余额的时间 (ISO 8601 格式) */
+  balanceCheckedAt?: string;
+  /** 余额是否已过时（超过 staleAfterMinutes 未成功查询，未过时时省略） */
+  balanceStale?: boolean;
+  /** 最近一次查询失败的原因（查询成功后清除） */
+  lastError?: string;
+  /** 最近一次查询失败的时间 (ISO 8601 格式) */
+  lastErrorAt?: string;
+  /** 连续查询失败次数 */
+  consecutiveFailures: number;
   /** 是否为当前激活的密钥 */
   isActive: boolean;
+  /** 是否已过期（根据余额中的到期时间自动标记） */
+  expired: boolean;
+  /** 密钥状态（根据每次余额查询的结果更新） */
+  status: ProviderStatus;
   /** 创建时间 (ISO 8601 格式) */
   createdAt?: string;
   /** 更新时间 (ISO 8601 格式) */
   updatedAt?: string;
+  /** 该密钥的额度提醒阈值（已用百分比，为空使用全局设置） */
+  alertThresholds?: number[];
 }
 
 /**
@@ -46,6 +62,207 @@ export interface BalanceInfo {
   exceeded: boolean;
   /** 到期时间 (ISO 8601 格式, 可选) */
   expiryDate?: string;
+  /** 计费周期开始时间 (ISO 8601 格式, 可选) */
+  periodStart?: string;
+  /** 各档位用量（standard、premium 等），上面的汇总字段来自 standard */
+  tiers?: TierUsage[];
+  /** 接口原始响应（用于排查问题） */
+  raw?: unknown;
+}
+
+/**
+ * 单个用量档位的用量
+ */
+export interface TierUsage {
+  /** 档位名称，如 standard、premium */
+  name: string;
+  used: number;
+  allowance: number;
+  remaining: number;
+  overage: number;
+  usedRatio: number;
+  /** 组织在该档位的总用量 */
+  orgUsed?: number;
+  /** 组织超额用量上限 */
+  overageLimit?: number;
+}
+
+/**
+ * 错误代码
+ */
+export type AppErrorCode =
+  | 'NotFound'
+  | 'Duplicate'
+  | 'InvalidKey'
+  | 'InvalidInput'
+  | 'Unauthorized'
+  | 'Network'
+  | 'Timeout'
+  | 'Http'
+  | 'Io'
+  | 'Parse'
+  | 'Locked'
+  | 'Other';
+
+/**
+ * 后端命令返回的错误
+ */
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+  /** 结构化上下文，如 HTTP 状态码 { status }、未找到的对象 { entity, id } */
+  context?: Record<string, unknown>;
+}
+
+/**
+ * 单个 API Key 的余额查询结果（成功时有 balance，失败时有 error）
+ */
+export interface BalanceCheckResult {
+  balance?: BalanceInfo;
+  error?: AppError;
+}
+
+/**
+ * 余额刷新进度（balance-updated 事件）
+ */
+export interface BalanceUpdatedEvent {
+  providerId: string;
+  providerName: string;
+  balance?: BalanceInfo;
+  error?: AppError;
+  /** 本轮已完成的密钥数 */
+  completed: number;
+  /** 本轮需要查询的密钥数 */
+  total: number;
+}
+
+/**
+ * 额度提醒设置
+ */
+export interface QuotaAlertSettings {
+  enabled: boolean;
+  /** 提醒阈值（已用百分比） */
+  thresholds: number[];
+  /** 超额时提醒 */
+  notifyExceeded: boolean;
+  /** 已用百分比回落到阈值以下多少个百分点后才会再次提醒 */
+  hysteresisPercent: number;
+}
+
+/**
+ * 额度提醒（quota-alert 事件）
+ */
+export interface QuotaAlert {
+  providerId: string;
+  providerName: string;
+  /** 越过的阈值（超额提醒时为空） */
+  threshold?: number;
+  exceeded: boolean;
+  percentUsed: number;
+  remaining: number;
+  message: string;
+}
+
+/**
+ * 到期提醒设置
+ */
+export interface ExpirySettings {
+  enabled: boolean;
+  /** 在到期前多少天提醒 */
+  warnDays: number[];
+  /** 当前密钥过期时自动切换到其他密钥 */
+  switchWhenExpired: boolean;
+}
+
+/**
+ * 到期提醒（expiry-alert 事件）
+ */
+export interface ExpiryAlert {
+  providerId: string;
+  providerName: string;
+  /** 到期时间 (ISO 8601 格式) */
+  expiryDate: string;
+  /** 距到期的整天数（已过期时为负数或 0） */
+  daysUntilExpiry: number;
+  expired: boolean;
+  message: string;
+}
+
+/**
+ * 网络设置
+ */
+export interface NetworkSettings {
+  /** 代理地址（http://、https://、socks5:// 或 socks5h://） */
+  proxy?: string;
+  /** 不走代理的主机 */
+  noProxy: string[];
+  /** 额外信任的根证书（PEM 文件路径） */
+  caBundlePath?: string;
+  /** 请求超时（秒） */
+  timeoutSecs: number;
+}
+
+/**
+ * 连接测试失败的环节
+ */
+export type ConnectivityStage = 'config' | 'dns' | 'proxy' | 'connect' | 'tls' | 'timeout';
+
+/**
+ * 连接测试结果
+ */
+export interface ConnectivityReport {
+  baseUrl: string;
+  proxy?: string;
+  /** 是否收到 HTTP 响应（任何状态码都说明网络可达） */
+  ok: boolean;
+  status?: number;
+  elapsedMs: number;
+  failedStage?: ConnectivityStage;
+  error?: string;
+}
+
+/**
+ * 后台定时刷新余额设置
+ */
+export interface BalanceRefreshSettings {
+  enabled: boolean;
+  /** 刷新间隔（分钟） */
+  intervalMinutes: number;
+  /** 在此时间（分钟）内查询过的密钥跳过 */
+  ttlMinutes: number;
+  /** 超过此时间（分钟）未成功查询的余额标记为已过时 */
+  staleAfterMinutes: number;
+}
+
+/**
+ * 密钥状态
+ */
+export type ProviderStatus =
+  | 'unknown'
+  | 'valid'
+  | 'invalid'
+  | 'revoked'
+  | 'expired'
+  | 'exhausted';
+
+/**
+ * 单个密钥的校验结果
+ */
+export interface KeyValidation {
+  providerId: string;
+  providerName: string;
+  status: ProviderStatus;
+  error?: AppError;
+}
+
+/**
+ * 刷新所有余额的结果
+ */
+export interface RefreshAllResult {
+  /** 更新后的所有密钥列表 */
+  providers: Provider[];
+  /** 查询失败的密钥 ID -> 错误 */
+  errors: Record<string, AppError>;
 }
 
 /**
@@ -69,6 +286,46 @@ export interface ModelInfo {
   isBuiltin: boolean;
   /** 推理级别 */
   reasoningLevel: ReasoningLevel;
+  /** 计费倍率（如 1.2 表示每个 token 按 1.2 个计入额度） */
+  costMultiplier?: number;
+  /** 允许的推理级别（为空表示不限制） */
+  reasoningLevels?: ReasoningLevel[];
+  /** 内置模型已从新版本的内置列表中移除 */
+  deprecated: boolean;
+}
+
+/**
+ * 按模型倍率折算的剩余额度
+ */
+export interface ModelQuota {
+  modelId: string;
+  modelName: string;
+  costMultiplier: number;
+  /** 剩余额度可用于该模型的 tokens */
+  effectiveRemaining: number;
+}
+
+/**
+ * 外部模型清单设置
+ */
+export interface ModelCatalogSettings {
+  /** 模型清单来源：本地文件路径或 HTTP(S) 地址（为空使用内置模型列表） */
+  source?: string;
+  /** 自动重新获取清单的间隔（小时） */
+  refreshHours: number;
+}
+
+/**
+ * 当前使用的模型目录
+ */
+export interface ModelCatalogStatus {
+  source?: string;
+  /** 清单版本（使用内置模型列表时为空） */
+  version?: number;
+  modelCount: number;
+  fetchedAt?: string;
+  /** 是否在使用内置模型列表 */
+  fallback: boolean;
 }
 
 /**
@@ -111,9 +368,10 @@ export const DroidAPI = {
    * 添加密钥
    * @param name 密钥名称
    * @param apiKey API Key
+   * @param verify 添加前在线校验 API Key（查询一次余额）
    */
-  addProvider: (name: string, apiKey: string): Promise<Provider> =>
-    invoke('add_provider', { name, apiKey }),
+  addProvider: (name: string, apiKey: string, verify = false): Promise<Provider> =>
+    invoke('add_provider', { name, apiKey, verify }),
 
   /**
    * 删除密钥
@@ -153,9 +411,9 @@ export const DroidAPI = {
   /**
    * 批量查询多个 API Keys 的余额
    * @param apiKeys API Key 列表
-   * @returns 返回 Map<apiKey, BalanceInfo>
+   * @returns 返回 Map<apiKey, BalanceCheckResult>
    */
-  batchCheckBalances: (apiKeys: string[]): Promise<Record<string, BalanceInfo>> =>
+  batchCheckBalances: (apiKeys: string[]): Promise<Record<string, BalanceCheckResult>> =>
     invoke('batch_check_balances', { apiKeys }),
 
   /**
@@ -167,11 +425,86 @@ export const DroidAPI = {
 
   /**
    * 刷新所有密钥的余额
-   * @returns 返回更新后的所有密钥列表
+   * @returns 返回更新后的所有密钥列表和查询失败的密钥
    */
-  refreshAllBalances: (): Promise<Provider[]> =>
+  refreshAllBalances: (): Promise<RefreshAllResult> =>
     invoke('refresh_all_balances'),
 
+  /**
+   * 重新校验所有密钥（忽略刷新 TTL）
+   * @returns 每个密钥更新后的状态
+   */
+  validateAllProviders: (): Promise<KeyValidation[]> =>
+    invoke('validate_all_providers'),
+
+  /**
+   * 获取后台定时刷新设置
+   */
+  getBalanceRefreshSettings: (): Promise<BalanceRefreshSettings> =>
+    invoke('get_balance_refresh_settings'),
+
+  /**
+   * 保存后台定时刷新设置
+   * @param settings 刷新设置
+   */
+  setBalanceRefreshSettings: (settings: BalanceRefreshSettings): Promise<void> =>
+    invoke('set_balance_refresh_settings', { settings }),
+
+  /**
+   * 获取额度提醒设置
+   */
+  getQuotaAlertSettings: (): Promise<QuotaAlertSettings> =>
+    invoke('get_quota_alert_settings'),
+
+  /**
+   * 保存额度提醒设置
+   * @param settings 提醒设置
+   */
+  setQuotaAlertSettings: (settings: QuotaAlertSettings): Promise<void> =>
+    invoke('set_quota_alert_settings', { settings }),
+
+  /**
+   * 设置密钥单独的额度提醒阈值
+   * @param id 密钥 ID
+   * @param thresholds 已用百分比阈值，传 null 使用全局设置
+   */
+  setProviderAlertThresholds: (id: string, thresholds: number[] | null): Promise<void> =>
+    invoke('set_provider_alert_thresholds', { id, thresholds }),
+
+  /**
+   * 获取到期提醒设置
+   */
+  getExpirySettings: (): Promise<ExpirySettings> =>
+    invoke('get_expiry_settings'),
+
+  /**
+   * 保存到期提醒设置
+   * @param settings 提醒设置
+   */
+  setExpirySettings: (settings: ExpirySettings): Promise<void> =>
+    invoke('set_expiry_settings', { settings }),
+
+  // ==================== 网络设置 ====================
+
+  /**
+   * 获取网络设置
+   */
+  getNetworkSettings: (): Promise<NetworkSettings> =>
+    invoke('get_network_settings'),
+
+  /**
+   * 保存网络设置（代理、证书、超时）
+   * @param settings 网络设置
+   */
+  setNetworkSettings: (settings: NetworkSettings): Promise<void> =>
+    invoke('set_network_settings', { settings }),
+
+  /**
+   * 测试能否连接 Factory API
+   */
+  testConnectivity: (): Promise<ConnectivityReport> =>
+    invoke('test_connectivity'),
+
   // ==================== 环境变量管理 ====================
 
   /**
@@ -208,9 +541,17 @@ export const DroidAPI = {
    * @param provider 提供商名称
    * @param description 模型描述 (可选)
    * @param reasoningLevel 推理级别 (可选)
+   * @param costMultiplier 计费倍率 (可选)
+   */
+  addCustomModel: (id: string, name: string, provider: string, description?: string, reasoningLevel?: ReasoningLevel, costMultiplier?: number): Promise<void> =>
+    invoke('add_custom_model', { id, name, provider, description, reasoningLevel, costMultiplier }),
+
+  /**
+   * 获取密钥剩余额度在各模型下实际可用的 tokens（按可用量从多到少）
+   * @param providerId 密钥 ID
    */
-  addCustomModel: (id: string, name: string, provider: string, description?: string, reasoningLevel?: ReasoningLevel): Promise<void> =>
-    invoke('add_custom_model', { id, name, provider, description, reasoningLevel }),
+  getModelQuotas: (providerId: string): Promise<ModelQuota[]> =>
+    invoke('get_model_quotas', { providerId }),
 
   /**
    * 删除自定义模型
@@ -232,6 +573,30 @@ export const DroidAPI = {
    */
   resetModelsConfig: (): Promise<void> =>
     invoke('reset_models_config'),
+
+  /**
+   * 获取外部模型清单设置
+   */
+  getModelCatalogSettings: (): Promise<ModelCatalogSettings> =>
+    invoke('get_model_catalog_settings'),
+
+  /**
+   * 保存外部模型清单设置并立即获取清单
+   */
+  setModelCatalogSettings: (settings: ModelCatalogSettings): Promise<ModelCatalogStatus> =>
+    invoke('set_model_catalog_settings', { settings }),
+
+  /**
+   * 获取当前使用的模型目录
+   */
+  getModelCatalogStatus: (): Promise<ModelCatalogStatus> =>
+    invoke('get_model_catalog_status'),
+
+  /**
+   * 重新获取模型清单
+   */
+  refreshModelCatalog: (): Promise<ModelCatalogStatus> =>
+    invoke('refresh_model_catalog'),
 };
 
 export default DroidAPI;